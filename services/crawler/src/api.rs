@@ -1,13 +1,31 @@
-use actix_web::{web, get, post, HttpResponse, Responder};
+use actix_web::{web, get, post, delete, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::crawler::Crawler;
+use crate::crawler::{Crawler, CrawlJobSpec, CrawlStatus};
+use crate::scope::CrawlScope;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use storage::PostgresStorage;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct CrawlRequest {
-    pub query: String,
+    /// Also start from the stored pages matching this query
+    #[serde(default)]
+    pub query: Option<String>,
+    /// URLs to start crawling from
+    #[serde(default)]
+    pub seed_urls: Vec<String>,
+    /// Only crawl these domains and their subdomains (empty means all)
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// Never crawl these domains and their subdomains
+    #[serde(default)]
+    pub denied_domains: Vec<String>,
+    /// Only crawl URLs matching at least one of these regexes (empty means all)
+    #[serde(default)]
+    pub include_patterns: Vec<String>,
+    /// Never crawl URLs matching any of these regexes
+    #[serde(default)]
+    pub exclude_patterns: Vec<String>,
     pub max_depth: usize,
     pub max_pages: usize,
     #[serde(default = "default_priority")]
@@ -34,93 +52,192 @@ pub struct CrawlResponse {
     pub suggested_queries: Vec<String>,
 }
 
+#[derive(Deserialize)]
+pub struct JobListQuery {
+    #[serde(default = "default_job_limit")]
+    pub limit: i64,
+}
+
+fn default_job_limit() -> i64 {
+    50
+}
+
 #[post("/crawl")]
 pub async fn crawl(
-    crawler: web::Data<Arc<Mutex<Crawler>>>,
+    crawler: web::Data<Arc<Crawler>>,
+    storage: web::Data<PostgresStorage>,
     request: web::Json<CrawlRequest>,
 ) -> impl Responder {
-    let job_id = Uuid::new_v4().to_string();
-    let request_query = request.query.clone();
+    let job_uuid = Uuid::new_v4();
+    let job_id = job_uuid.to_string();
+    let request_query = request.query.as_deref().map(str::trim).filter(|q| !q.is_empty()).map(str::to_string);
     let max_depth = request.max_depth;
     let max_pages = request.max_pages;
     let priority = request.priority;
     let force_crawl = request.force_crawl;
-    
-    let crawler_guard = crawler.lock().await;
-    
+
+    if request_query.is_none() && request.seed_urls.is_empty() {
+        return HttpResponse::BadRequest().json(error_response(job_id, "A crawl needs a query or seed URLs".to_string()));
+    }
+
+    for url in &request.seed_urls {
+        if let Err(e) = crawler.check_seed_url(url).await {
+            return HttpResponse::BadRequest().json(error_response(job_id, format!("Cannot crawl {}: {}", url, e)));
+        }
+    }
+
+    let scope = match CrawlScope::new(
+        &request.allowed_domains,
+        &request.denied_domains,
+        &request.include_patterns,
+        &request.exclude_patterns,
+    ) {
+        Ok(scope) => scope,
+        Err(e) => return HttpResponse::BadRequest().json(error_response(job_id, e.to_string())),
+    };
+
     // First check for existing results
-    match crawler_guard.check_existing_results(&request_query).await {
-        Ok(crawl_status) => {
-            if !crawl_status.has_results && !force_crawl {
-                // Return immediately with suggestions if no results and not forcing crawl
-                return HttpResponse::Ok().json(CrawlResponse {
-                    job_id,
-                    status: "no_results".to_string(),
-                    message: crawl_status.message,
-                    has_results: false,
-                    existing_results_count: 0,
-                    suggested_queries: crawl_status.suggested_queries,
-                });
+    let crawl_status = match &request_query {
+        Some(query) => match crawler.check_existing_results(query).await {
+            Ok(crawl_status) => crawl_status,
+            Err(e) => {
+                return HttpResponse::InternalServerError().json(error_response(job_id, format!("Error checking results: {}", e)));
             }
-            
-            // Either we have results or force_crawl is true
-            let crawler_clone = crawler.clone();
-            let job_id_clone = job_id.clone();
-            let query_clone = request_query.clone();
-            
-            tokio::spawn(async move {
-                let crawler_guard = crawler_clone.lock().await;
-                if let Err(e) = crawler_guard.crawl_for_query(&query_clone, max_depth, max_pages).await {
-                    log::error!("Crawl error for job {}: {}", job_id_clone, e);
-                }
-            });
-
-            HttpResponse::Ok().json(CrawlResponse {
-                job_id,
-                status: "queued".to_string(),
-                message: format!(
-                    "Crawl job started for query: {} with max_depth: {}, max_pages: {}, priority: {}", 
-                    request_query, max_depth, max_pages, priority
-                ),
-                has_results: crawl_status.has_results,
-                existing_results_count: crawl_status.existing_results_count,
-                suggested_queries: crawl_status.suggested_queries,
-            })
-        }
-        Err(e) => {
-            HttpResponse::InternalServerError().json(CrawlResponse {
-                job_id,
-                status: "error".to_string(),
-                message: format!("Error checking results: {}", e),
-                has_results: false,
-                existing_results_count: 0,
-                suggested_queries: vec![],
-            })
+        },
+        None => CrawlStatus {
+            has_results: false,
+            existing_results_count: 0,
+            suggested_queries: vec![],
+            message: String::new(),
+        },
+    };
+
+    // Query-driven discovery starts from stored pages, so without results
+    // or explicit seeds there is nothing to crawl from
+    if request_query.is_some() && !crawl_status.has_results && request.seed_urls.is_empty() && !force_crawl {
+        // Return immediately with suggestions if no results and not forcing crawl
+        return HttpResponse::Ok().json(CrawlResponse {
+            job_id,
+            status: "no_results".to_string(),
+            message: crawl_status.message,
+            has_results: false,
+            existing_results_count: 0,
+            suggested_queries: crawl_status.suggested_queries,
+        });
+    }
+
+    let params = serde_json::to_value(&*request).unwrap_or_default();
+    if let Err(e) = storage.create_crawl_job(job_uuid, crawler.instance_id(), &params).await {
+        return HttpResponse::InternalServerError().json(error_response(job_id, format!("Error creating crawl job: {}", e)));
+    }
+
+    let spec = CrawlJobSpec {
+        seed_urls: request.seed_urls.clone(),
+        query: request_query.clone(),
+        scope,
+        max_depth,
+        max_pages,
+    };
+    let crawler_clone = crawler.get_ref().clone();
+    let job_id_clone = job_id.clone();
+
+    // Jobs run independently of each other and of this request
+    tokio::spawn(async move {
+        if let Err(e) = crawler_clone.run_job(job_uuid, spec).await {
+            log::error!("Crawl error for job {}: {}", job_id_clone, e);
         }
+    });
+
+    HttpResponse::Ok().json(CrawlResponse {
+        job_id,
+        status: "queued".to_string(),
+        message: format!(
+            "Crawl job started for query: {} with {} seed URLs, max_depth: {}, max_pages: {}, priority: {}",
+            request_query.as_deref().unwrap_or("-"), request.seed_urls.len(), max_depth, max_pages, priority
+        ),
+        has_results: crawl_status.has_results,
+        existing_results_count: crawl_status.existing_results_count,
+        suggested_queries: crawl_status.suggested_queries,
+    })
+}
+
+fn error_response(job_id: String, message: String) -> CrawlResponse {
+    CrawlResponse {
+        job_id,
+        status: "error".to_string(),
+        message,
+        has_results: false,
+        existing_results_count: 0,
+        suggested_queries: vec![],
+    }
+}
+
+#[get("/jobs")]
+pub async fn list_jobs(
+    storage: web::Data<PostgresStorage>,
+    query: web::Query<JobListQuery>,
+) -> impl Responder {
+    match storage.list_crawl_jobs(query.limit.clamp(1, 500)).await {
+        Ok(jobs) => HttpResponse::Ok().json(jobs),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Error listing jobs: {}", e)
+        })),
+    }
+}
+
+#[get("/jobs/{job_id}")]
+pub async fn get_job(
+    storage: web::Data<PostgresStorage>,
+    id: web::Path<Uuid>,
+) -> impl Responder {
+    job_response(&storage, id.into_inner()).await
+}
+
+/// Cancels a queued or running job. A running job stops after its current batch.
+#[delete("/jobs/{job_id}")]
+pub async fn cancel_job(
+    storage: web::Data<PostgresStorage>,
+    id: web::Path<Uuid>,
+) -> impl Responder {
+    let id = id.into_inner();
+    match storage.cancel_crawl_job(id).await {
+        Ok(Some(job)) => HttpResponse::Ok().json(job),
+        Ok(None) => match storage.get_crawl_job(id).await {
+            Ok(Some(job)) => HttpResponse::Conflict().json(serde_json::json!({
+                "error": format!("Job {} has already finished", id),
+                "job": job
+            })),
+            Ok(None) => job_not_found(id),
+            Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": format!("Error loading job: {}", e)
+            })),
+        },
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Error cancelling job: {}", e)
+        })),
     }
 }
 
 #[get("/job-status/{job_id}")]
 pub async fn get_job_status(
-    crawler: web::Data<Arc<Mutex<Crawler>>>,
-    id: web::Path<String>
+    storage: web::Data<PostgresStorage>,
+    id: web::Path<Uuid>,
 ) -> impl Responder {
-    let crawler = crawler.lock().await;
-    let queue_size = crawler.get_queue_size().await;
-    let pages_crawled = crawler.get_visited_count().await;
-    
-    let status = if queue_size == 0 && pages_crawled > 0 {
-        "completed"
-    } else if pages_crawled > 0 {
-        "in_progress"
-    } else {
-        "starting"
-    };
+    job_response(&storage, id.into_inner()).await
+}
+
+async fn job_response(storage: &PostgresStorage, id: Uuid) -> HttpResponse {
+    match storage.get_crawl_job(id).await {
+        Ok(Some(job)) => HttpResponse::Ok().json(job),
+        Ok(None) => job_not_found(id),
+        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Error loading job: {}", e)
+        })),
+    }
+}
 
-    HttpResponse::Ok().json(serde_json::json!({
-        "job_id": id.into_inner(),
-        "status": status,
-        "pages_crawled": pages_crawled,
-        "queue_size": queue_size
+fn job_not_found(id: Uuid) -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": format!("Job {} not found", id)
     }))
 }
@@ -0,0 +1,290 @@
+use chrono::{Datelike, Utc};
+use crate::error::CrawlerError;
+use url::Url;
+
+/// Query parameters removed by default: campaign tracking, click ids and
+/// session ids. A trailing `*` matches any parameter with that prefix. Short
+/// names that sites also use for content (`sid`, `ref`) are left alone.
+pub const DEFAULT_STRIP_PARAMS: &[&str] = &[
+    "utm_*", "fbclid", "gclid", "gclsrc", "dclid", "msclkid", "yclid", "mc_cid", "mc_eid",
+    "_ga", "_gl", "igshid", "ref_src", "sessionid", "session_id", "phpsessid",
+    "jsessionid", "aspsessionid", "cfid", "cftoken",
+];
+
+/// URLs longer than this are generated rather than linked by hand.
+const MAX_URL_LENGTH: usize = 2048;
+
+/// Paths nested deeper than this are taken for a trap.
+const MAX_PATH_SEGMENTS: usize = 32;
+
+/// How often one (non-numeric) path segment may appear before the path is
+/// taken for a loop of relative links, as in `/a/b/a/b/a/b`.
+const MAX_SEGMENT_REPEATS: usize = 2;
+
+/// Faceted navigation combining this many parameters explodes into endless
+/// variants of the same listing.
+const MAX_QUERY_PARAMS: usize = 16;
+
+/// Highest page number followed in paginated listings.
+const MAX_PAGE_NUMBER: u64 = 1000;
+
+/// Highest item offset followed in paginated listings.
+const MAX_ITEM_OFFSET: u64 = 50_000;
+
+/// Calendars are followed this many years back and ahead of the current year.
+const CALENDAR_YEARS_BACK: i32 = 30;
+const CALENDAR_YEARS_AHEAD: i32 = 2;
+
+const PAGE_PARAMS: &[&str] = &["page", "pg", "paged", "pagenum", "page_num", "pageno", "pagenumber"];
+const OFFSET_PARAMS: &[&str] = &["offset", "start", "skip", "from"];
+const CALENDAR_PARAMS: &[&str] = &["year", "month", "day", "date", "cal", "calendar", "week", "ym"];
+
+/// Turns URLs into one canonical form, so that the same resource is stored
+/// and queued once, and keeps crawl traps out of the frontier. The canonical
+/// form is a key, not what is requested; see [`fetch_url`].
+///
+/// Normalization follows RFC 3986 section 6: scheme and host are lowercased,
+/// default ports and dot segments removed, percent-encodings of unreserved
+/// characters decoded and the others uppercased. Paths and query values keep
+/// their case, which servers are free to depend on. Beyond the RFC, the
+/// fragment is dropped, configured tracking and session parameters are
+/// removed (also `;jsessionid=` path parameters) and the remaining query
+/// parameters are sorted by name.
+#[derive(Debug, Clone)]
+pub struct UrlCanonicalizer {
+    strip_params: Vec<StripRule>,
+}
+
+#[derive(Debug, Clone)]
+enum StripRule {
+    Exact(String),
+    Prefix(String),
+}
+
+impl StripRule {
+    fn parse(pattern: &str) -> Option<Self> {
+        let pattern = pattern.trim().to_ascii_lowercase();
+        match pattern.strip_suffix('*') {
+            Some("") => None,
+            Some(prefix) => Some(StripRule::Prefix(prefix.to_string())),
+            None if pattern.is_empty() => None,
+            None => Some(StripRule::Exact(pattern)),
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            StripRule::Exact(exact) => name.eq_ignore_ascii_case(exact),
+            StripRule::Prefix(prefix) => name.to_ascii_lowercase().starts_with(prefix.as_str()),
+        }
+    }
+}
+
+impl Default for UrlCanonicalizer {
+    fn default() -> Self {
+        Self::new(DEFAULT_STRIP_PARAMS)
+    }
+}
+
+impl UrlCanonicalizer {
+    /// A canonicalizer removing the query parameters matching `strip_params`.
+    pub fn new<S: AsRef<str>>(strip_params: &[S]) -> Self {
+        UrlCanonicalizer {
+            strip_params: strip_params.iter().filter_map(|p| StripRule::parse(p.as_ref())).collect(),
+        }
+    }
+
+    /// The canonical form of an absolute http(s) URL.
+    pub fn canonicalize(&self, url: &str) -> Result<String, CrawlerError> {
+        let mut url = Url::parse(url.trim()).map_err(|e| CrawlerError::UrlNormalizationError(e.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(CrawlerError::UrlNormalizationError(format!("unsupported scheme '{}'", url.scheme())));
+        }
+
+        url.set_fragment(None);
+        if let Some(host) = url.host_str().filter(|host| host.ends_with('.')).map(|host| host.trim_end_matches('.').to_string()) {
+            url.set_host(Some(&host)).map_err(|e| CrawlerError::UrlNormalizationError(e.to_string()))?;
+        }
+
+        let path = normalize_percent_encoding(&strip_path_session(url.path()));
+        url.set_path(&path);
+
+        let query = url.query().map(|query| self.normalize_query(query));
+        url.set_query(query.as_deref().filter(|query| !query.is_empty()));
+
+        Ok(url.to_string())
+    }
+
+    /// The canonical form of a URL about to be queued for crawling, unless it
+    /// looks like a crawl trap.
+    pub fn canonicalize_for_frontier(&self, url: &str) -> Result<String, CrawlerError> {
+        let canonical = self.canonicalize(url)?;
+        match crawl_trap(&canonical) {
+            Some(reason) => Err(CrawlerError::CrawlTrap(format!("{}: {}", canonical, reason))),
+            None => Ok(canonical),
+        }
+    }
+
+    /// Drops stripped and empty parameters and sorts the rest by name. The
+    /// sort is stable, so repeated parameters keep their order.
+    fn normalize_query(&self, query: &str) -> String {
+        let mut params: Vec<(String, &str)> = query
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .map(|param| (param_name(param), param))
+            .filter(|(name, _)| !self.strip_params.iter().any(|rule| rule.matches(name)))
+            .collect();
+        params.sort_by(|a, b| a.0.cmp(&b.0));
+        params.into_iter()
+            .map(|(_, param)| normalize_percent_encoding(param))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+/// The URL to request for `url`: as it was found, only without its fragment.
+/// Servers may depend on the parameters, their order and their encoding, so
+/// the canonical form serves as a key for storing and queueing only.
+pub fn fetch_url(url: &str) -> Result<String, CrawlerError> {
+    let mut url = Url::parse(url.trim()).map_err(|e| CrawlerError::UrlNormalizationError(e.to_string()))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(CrawlerError::UrlNormalizationError(format!("unsupported scheme '{}'", url.scheme())));
+    }
+    url.set_fragment(None);
+    Ok(url.to_string())
+}
+
+/// The key pages were stored under before URLs were canonicalized: the URL
+/// lowercased, without default port or trailing slash, and with its query
+/// parameters sorted and re-encoded. Pages still stored under such a key are
+/// moved to their canonical one when they are crawled again.
+pub fn legacy_url_key(url: &str) -> Option<String> {
+    let mut url = Url::parse(url).ok()?;
+    let path = url.path().trim_end_matches('/').to_string();
+    url.set_path(&path);
+    if let Some(query) = url.query() {
+        let mut params: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+        params.sort_by(|a, b| a.0.cmp(&b.0));
+        let query = url::form_urlencoded::Serializer::new(String::new()).extend_pairs(params).finish();
+        url.set_query(Some(&query));
+    }
+    Some(url.to_string().to_lowercase())
+}
+
+/// Why a canonical URL looks like a crawl trap, if it does.
+pub fn crawl_trap(url: &str) -> Option<String> {
+    if url.len() > MAX_URL_LENGTH {
+        return Some(format!("longer than {} characters", MAX_URL_LENGTH));
+    }
+    let parsed = Url::parse(url).ok()?;
+
+    let segments: Vec<&str> = parsed.path_segments().map(|s| s.filter(|s| !s.is_empty()).collect()).unwrap_or_default();
+    if segments.len() > MAX_PATH_SEGMENTS {
+        return Some(format!("more than {} path segments", MAX_PATH_SEGMENTS));
+    }
+    for (i, segment) in segments.iter().enumerate() {
+        if !segment.bytes().all(|b| b.is_ascii_digit())
+            && segments.iter().filter(|s| s.eq_ignore_ascii_case(segment)).count() > MAX_SEGMENT_REPEATS
+        {
+            return Some(format!("path segment '{}' repeats", segment));
+        }
+        let next = segments.get(i + 1).copied();
+        if segment.eq_ignore_ascii_case("page") && next.and_then(|n| n.parse::<u64>().ok()).is_some_and(|n| n > MAX_PAGE_NUMBER) {
+            return Some(format!("page number above {}", MAX_PAGE_NUMBER));
+        }
+        // Archive paths such as /2024/05/
+        let is_month = |n: &str| n.len() <= 2 && n.parse::<u32>().is_ok_and(|month| (1..=12).contains(&month));
+        if segment.len() == 4 && next.is_some_and(is_month) {
+            if let Some(year) = segment.parse::<i32>().ok().filter(|year| !is_plausible_year(*year)) {
+                return Some(format!("calendar year {}", year));
+            }
+        }
+    }
+
+    let params: Vec<(String, String)> = parsed.query_pairs().map(|(k, v)| (k.to_lowercase(), v.into_owned())).collect();
+    if params.len() > MAX_QUERY_PARAMS {
+        return Some(format!("more than {} query parameters", MAX_QUERY_PARAMS));
+    }
+    for (name, value) in &params {
+        let number = value.trim().parse::<u64>().ok();
+        if PAGE_PARAMS.contains(&name.as_str()) && number.is_some_and(|n| n > MAX_PAGE_NUMBER) {
+            return Some(format!("{} above {}", name, MAX_PAGE_NUMBER));
+        }
+        if OFFSET_PARAMS.contains(&name.as_str()) && number.is_some_and(|n| n > MAX_ITEM_OFFSET) {
+            return Some(format!("{} above {}", name, MAX_ITEM_OFFSET));
+        }
+        if CALENDAR_PARAMS.contains(&name.as_str()) {
+            if let Some(year) = leading_year(value).filter(|year| !is_plausible_year(*year)) {
+                return Some(format!("calendar year {}", year));
+            }
+        }
+    }
+    None
+}
+
+fn is_plausible_year(year: i32) -> bool {
+    let current = Utc::now().year();
+    (current - CALENDAR_YEARS_BACK..=current + CALENDAR_YEARS_AHEAD).contains(&year)
+}
+
+/// The year a calendar parameter value starts with (`2031`, `2031-04`,
+/// `203104`).
+fn leading_year(value: &str) -> Option<i32> {
+    let digits: String = value.trim().chars().take_while(char::is_ascii_digit).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+    digits[..4].parse().ok()
+}
+
+/// The decoded, lowercased name of a `name=value` query parameter.
+fn param_name(param: &str) -> String {
+    let name = param.split('=').next().unwrap_or(param);
+    url::form_urlencoded::parse(name.as_bytes())
+        .next()
+        .map(|(name, _)| name.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Removes Java-style `;jsessionid=...` path parameters.
+fn strip_path_session(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.to_ascii_lowercase().find(";jsessionid=") {
+            Some(i) => &segment[..i],
+            None => segment,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Decodes percent-encoded unreserved characters (letters, digits, `-`,
+/// `.`, `_`, `~`) and uppercases the hex digits of all other escapes.
+fn normalize_percent_encoding(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| Some((hex_value(*bytes.get(i + 1)?)?, hex_value(*bytes.get(i + 2)?)?)))
+            .flatten();
+        if let Some((high, low)) = escape {
+            let value = high * 16 + low;
+            if value.is_ascii_alphanumeric() || matches!(value, b'-' | b'.' | b'_' | b'~') {
+                output.push(value as char);
+            } else {
+                output.push('%');
+                output.push(bytes[i + 1].to_ascii_uppercase() as char);
+                output.push(bytes[i + 2].to_ascii_uppercase() as char);
+            }
+            i += 3;
+        } else if let Some(ch) = input[i..].chars().next() {
+            output.push(ch);
+            i += ch.len_utf8();
+        }
+    }
+    output
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|d| d as u8)
+}
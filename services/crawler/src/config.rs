@@ -1,3 +1,4 @@
+use lazy_static::lazy_static;
 use serde::Deserialize;
 use std::fs::File;
 use std::io::Read;
@@ -5,6 +6,13 @@ use structopt::StructOpt;
 use std::error::Error;
 use std::path::Path;
 use url::Url;
+use crate::canonicalizer;
+use crate::quality;
+
+lazy_static! {
+    // Command-line default of `strip_query_params`
+    static ref DEFAULT_STRIP_PARAMS_ARG: String = canonicalizer::DEFAULT_STRIP_PARAMS.join(",");
+}
 
 /// Struct `Config` represents the configuration options for a web crawler.
 /// It holds parameters like the start URLs, crawl depth, number of pages to fetch,
@@ -64,6 +72,178 @@ pub struct Config {
     /// List of blocked domains
     #[structopt(long, use_delimiter = true)]
     pub blocked_domains: Option<Vec<String>>,
+
+    /// Share of each crawl batch spent on revisiting known pages (0.0 - 1.0).
+    /// Revisits may use the whole batch when there is nothing new to crawl.
+    #[structopt(long, default_value = "0.2")]
+    #[serde(default = "default_revisit_ratio")]
+    pub revisit_ratio: f64,
+
+    /// Shortest time between two fetches of the same page, in hours
+    #[structopt(long, default_value = "6")]
+    #[serde(default = "default_min_revisit_hours")]
+    pub min_revisit_hours: u64,
+
+    /// Longest time between two fetches of the same page, in hours
+    #[structopt(long, default_value = "720")]
+    #[serde(default = "default_max_revisit_hours")]
+    pub max_revisit_hours: u64,
+
+    /// Discover URLs from the sitemaps of each newly seen host
+    #[structopt(long, parse(try_from_str), default_value = "true")]
+    #[serde(default = "default_use_sitemaps")]
+    pub use_sitemaps: bool,
+
+    /// Largest SimHash Hamming distance (in bits, out of 64) at which two
+    /// pages count as near-duplicates
+    #[structopt(long, default_value = "3")]
+    #[serde(default = "default_max_duplicate_distance")]
+    pub max_duplicate_distance: u32,
+
+    /// Longest page summary, in characters
+    #[structopt(long, default_value = "300")]
+    #[serde(default = "default_summary_max_chars")]
+    pub summary_max_chars: usize,
+
+    /// Use a page's meta description as its summary when it has a usable one
+    #[structopt(long, parse(try_from_str), default_value = "true")]
+    #[serde(default = "default_prefer_meta_description")]
+    pub prefer_meta_description: bool,
+
+    /// Query parameters removed from the canonical URLs that pages are stored
+    /// and queued under (tracking and session ids); pages are still requested
+    /// as found. A trailing `*` matches a name prefix.
+    #[structopt(long, use_delimiter = true, default_value = DEFAULT_STRIP_PARAMS_ARG.as_str())]
+    #[serde(default = "default_strip_query_params")]
+    pub strip_query_params: Vec<String>,
+
+    /// Ports that pages may be fetched from
+    #[structopt(long, use_delimiter = true, default_value = "80,443")]
+    #[serde(default = "default_allowed_ports")]
+    pub allowed_ports: Vec<u16>,
+
+    /// Hosts (`*.` for all subdomains), addresses and CIDR networks exempt
+    /// from the port and private address checks, e.g. internal test sites
+    #[structopt(long, use_delimiter = true)]
+    #[serde(default)]
+    pub network_allowlist: Vec<String>,
+
+    /// Weights and thresholds of the page quality score, from the `[quality]`
+    /// section of the configuration file
+    #[structopt(skip)]
+    #[serde(default)]
+    pub quality: QualityConfig,
+}
+
+/// Settings of [`quality::WeightedQualityScorer`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    /// Points each signal is worth at its best
+    pub weights: QualityWeights,
+    /// Word count of the main content at which its length earns full points
+    pub full_content_words: usize,
+    /// Number of links at which the links earn full points
+    pub full_link_count: usize,
+    /// Text-to-HTML ratio (in bytes) below which the text ratio earns nothing
+    pub min_text_ratio: f64,
+    /// Text-to-HTML ratio at which the text ratio earns full points
+    pub full_text_ratio: f64,
+    /// Languages pages are wanted in, as primary subtags such as `en`. Empty
+    /// means any language.
+    pub languages: Vec<String>,
+    /// Words and phrases typical of spam, matched as whole words. Defaults to
+    /// [`quality::DEFAULT_SPAM_KEYWORDS`].
+    pub spam_keywords: Vec<String>,
+    /// Spam keyword hits per 1000 words at which a page loses all spam points
+    pub max_spam_density: f64,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        QualityConfig {
+            weights: QualityWeights::default(),
+            full_content_words: 1000,
+            full_link_count: 50,
+            min_text_ratio: 0.02,
+            full_text_ratio: 0.15,
+            languages: Vec::new(),
+            spam_keywords: quality::DEFAULT_SPAM_KEYWORDS.iter().map(|keyword| keyword.to_string()).collect(),
+            max_spam_density: 5.0,
+        }
+    }
+}
+
+/// Points each quality signal is worth. The defaults add up to 100, the
+/// scale `min_quality_score` is given on.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QualityWeights {
+    pub content_length: f64,
+    pub title: f64,
+    pub meta_description: f64,
+    pub meta_keywords: f64,
+    pub metadata: f64,
+    pub links: f64,
+    pub priority_domain: f64,
+    pub text_ratio: f64,
+    pub language: f64,
+    pub uniqueness: f64,
+    pub no_spam: f64,
+}
+
+impl Default for QualityWeights {
+    fn default() -> Self {
+        QualityWeights {
+            content_length: 25.0,
+            title: 5.0,
+            meta_description: 5.0,
+            meta_keywords: 2.0,
+            metadata: 5.0,
+            links: 10.0,
+            priority_domain: 20.0,
+            text_ratio: 15.0,
+            language: 5.0,
+            uniqueness: 5.0,
+            no_spam: 3.0,
+        }
+    }
+}
+
+fn default_revisit_ratio() -> f64 {
+    0.2
+}
+
+fn default_min_revisit_hours() -> u64 {
+    6
+}
+
+fn default_max_revisit_hours() -> u64 {
+    720
+}
+
+fn default_use_sitemaps() -> bool {
+    true
+}
+
+fn default_max_duplicate_distance() -> u32 {
+    3
+}
+
+fn default_summary_max_chars() -> usize {
+    300
+}
+
+fn default_prefer_meta_description() -> bool {
+    true
+}
+
+fn default_strip_query_params() -> Vec<String> {
+    canonicalizer::DEFAULT_STRIP_PARAMS.iter().map(|param| param.to_string()).collect()
+}
+
+fn default_allowed_ports() -> Vec<u16> {
+    vec![80, 443]
 }
 
 impl Config {
@@ -0,0 +1,374 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use scraper::node::Element;
+use scraper::{ElementRef, Html, Node};
+use serde::Serialize;
+use crate::parser::count_words;
+
+/// Blocks where more than this share of the text is link text are
+/// navigation, tag clouds or link lists.
+const MAX_LINK_DENSITY: f64 = 0.5;
+
+/// Highest link density of a block that counts as good on its own.
+const MAX_GOOD_LINK_DENSITY: f64 = 0.25;
+
+/// Blocks with fewer words are buttons, bylines, breadcrumbs and the like,
+/// and only kept between good blocks.
+const MIN_BLOCK_WORDS: usize = 7;
+
+/// A block needs this much text to count as good on its own.
+const MIN_GOOD_CHARS: usize = 100;
+
+/// Prose blocks (`<p>`, `<blockquote>`, ...) need less text to count as good.
+const MIN_GOOD_PROSE_CHARS: usize = 60;
+
+/// Words per line at which a block reads as running text rather than a list
+/// of labels.
+const MIN_GOOD_TEXT_DENSITY: f64 = 8.0;
+
+/// Line width used for the text density, as in Kohlschütter et al.
+const DENSITY_LINE_CHARS: usize = 80;
+
+/// How much text may separate a heading from the good block it introduces.
+const MAX_HEADING_DISTANCE_CHARS: usize = 200;
+
+/// Elements nested deeper than this are left out. Real pages stay far below
+/// it; it keeps crafted pages from exhausting the stack of the walk.
+const MAX_ELEMENT_DEPTH: usize = 256;
+
+const SKIPPED_TAGS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "math", "iframe", "object",
+    "embed", "canvas", "button", "select", "option", "textarea", "input", "map",
+];
+
+const BLOCK_TAGS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "body", "caption", "center", "dd", "details",
+    "dialog", "div", "dl", "dt", "fieldset", "figcaption", "figure", "footer", "form", "h1",
+    "h2", "h3", "h4", "h5", "h6", "header", "hgroup", "hr", "html", "legend", "li", "main",
+    "menu", "nav", "ol", "p", "pre", "section", "summary", "table", "tbody", "td", "tfoot",
+    "th", "thead", "tr", "ul",
+];
+
+const PROSE_TAGS: &[&str] = &["p", "blockquote", "pre", "dd", "figcaption"];
+
+const NEGATIVE_TAGS: &[&str] = &["nav", "footer", "aside", "form", "menu", "dialog"];
+
+const NEGATIVE_ROLES: &[&str] = &[
+    "navigation", "banner", "contentinfo", "complementary", "search", "menu", "menubar", "dialog", "alert",
+];
+
+lazy_static! {
+    // Class and id hints, after Readability
+    static ref NEGATIVE_HINT: Regex = Regex::new(
+        r"(?i)comment|sidebar|widget|footer|masthead|\bnav|menu|breadcrumb|share|social|sponsor|advert|\bads?\b|promo|related|recommend|cookie|consent|banner|popup|modal|newsletter|subscribe|signup|pagination|skip-link|toolbar"
+    ).unwrap();
+    static ref POSITIVE_HINT: Regex = Regex::new(
+        r"(?i)article|content|entry|main|post|text|story|blog|prose|markdown|body"
+    ).unwrap();
+}
+
+/// The main text of a page with its headings outline.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractedContent {
+    /// Text of the content blocks, one block per line
+    pub text: String,
+    /// Headings of the content, in document order
+    pub headings: Vec<Heading>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+}
+
+/// Extracts the main content of a page, leaving out navigation, sidebars,
+/// footers, comments and other boilerplate.
+///
+/// The page is cut into text blocks at block-level elements. Each block is
+/// classified by its link density, text density and tag, then by the blocks
+/// around it, in the manner of jusText: short and borderline blocks are kept
+/// only next to good ones, and headings only when they introduce good
+/// blocks. Blocks inside `<nav>`, `<footer>`, `<aside>` and elements whose
+/// class or id reads like boilerplate are dropped, unless that would leave
+/// nothing.
+pub fn extract(document: &Html) -> ExtractedContent {
+    let mut walker = Walker::default();
+    walker.walk(document.root_element(), Context::default(), 0);
+    walker.flush(Context::default(), None, false);
+    let blocks = walker.blocks;
+
+    let kept = select_blocks(&blocks, true);
+    let kept = if kept.is_empty() { select_blocks(&blocks, false) } else { kept };
+
+    let mut content = ExtractedContent::default();
+    let mut lines = Vec::new();
+    for block in kept.into_iter().map(|i| &blocks[i]) {
+        if let Some(level) = block.heading {
+            content.headings.push(Heading { level, text: block.text.clone() });
+        }
+        lines.push(block.text.as_str());
+    }
+    content.text = lines.join("\n");
+    content
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Bad,
+    Short,
+    NearGood,
+    Good,
+}
+
+struct Block {
+    text: String,
+    chars: usize,
+    link_chars: usize,
+    words: usize,
+    heading: Option<u8>,
+    prose: bool,
+    context: Context,
+}
+
+impl Block {
+    fn link_density(&self) -> f64 {
+        self.link_chars as f64 / self.chars.max(1) as f64
+    }
+
+    /// Words per line when the block is wrapped at a fixed width.
+    fn text_density(&self) -> f64 {
+        let lines = self.text.chars().count().div_ceil(DENSITY_LINE_CHARS).max(1);
+        self.words as f64 / lines as f64
+    }
+
+    fn classify(&self, use_context: bool) -> Class {
+        if use_context && self.context.negative {
+            return Class::Bad;
+        }
+        let link_density = self.link_density();
+        if link_density > MAX_LINK_DENSITY {
+            return Class::Bad;
+        }
+        if self.words < MIN_BLOCK_WORDS {
+            return if self.context.positive { Class::NearGood } else { Class::Short };
+        }
+
+        let min_chars = if self.prose { MIN_GOOD_PROSE_CHARS } else { MIN_GOOD_CHARS };
+        let good = self.chars >= min_chars
+            && link_density <= MAX_GOOD_LINK_DENSITY
+            && self.text_density() >= MIN_GOOD_TEXT_DENSITY;
+        if good || (self.context.positive && link_density <= MAX_GOOD_LINK_DENSITY) {
+            Class::Good
+        } else {
+            Class::NearGood
+        }
+    }
+}
+
+/// Indices of the blocks that make up the content.
+fn select_blocks(blocks: &[Block], use_context: bool) -> Vec<usize> {
+    let classes: Vec<Option<Class>> = blocks.iter()
+        .map(|block| block.heading.is_none().then(|| block.classify(use_context)))
+        .collect();
+
+    // The nearest good or bad block on each side, skipping headings and
+    // undecided blocks. The page edges count as bad.
+    let decided = |class: &Option<Class>| class.filter(|class| matches!(class, Class::Good | Class::Bad));
+    let before: Vec<Class> = classes.iter()
+        .scan(Class::Bad, |last, class| {
+            let previous = *last;
+            *last = decided(class).unwrap_or(previous);
+            Some(previous)
+        })
+        .collect();
+    let mut after: Vec<Class> = classes.iter().rev()
+        .scan(Class::Bad, |last, class| {
+            let next = *last;
+            *last = decided(class).unwrap_or(next);
+            Some(next)
+        })
+        .collect();
+    after.reverse();
+
+    let resolved: Vec<Option<Class>> = classes.iter().enumerate()
+        .map(|(i, class)| class.map(|class| match class {
+            Class::Short | Class::NearGood => {
+                let (before, after) = (before[i], after[i]);
+                let keep = match class {
+                    Class::Short => before == Class::Good && after == Class::Good,
+                    _ => before == Class::Good || after == Class::Good,
+                };
+                if keep { Class::Good } else { Class::Bad }
+            }
+            class => class,
+        }))
+        .collect();
+
+    (0..blocks.len())
+        .filter(|&i| match resolved[i] {
+            Some(class) => class == Class::Good,
+            None => !(use_context && blocks[i].context.negative) && introduces_content(blocks, &resolved, i),
+        })
+        .collect()
+}
+
+/// Whether a good block follows a heading closely, before the next heading.
+fn introduces_content(blocks: &[Block], resolved: &[Option<Class>], heading: usize) -> bool {
+    let mut distance = 0;
+    for i in heading + 1..blocks.len() {
+        match resolved[i] {
+            None => return false,
+            Some(Class::Good) => return true,
+            Some(_) => {
+                distance += blocks[i].chars;
+                if distance > MAX_HEADING_DISTANCE_CHARS {
+                    return false;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// What the elements around a piece of text say about it.
+#[derive(Debug, Clone, Copy, Default)]
+struct Context {
+    negative: bool,
+    positive: bool,
+    in_link: bool,
+}
+
+impl Context {
+    fn enter(self, element: &Element) -> Self {
+        let mut context = self;
+        let name = element.name();
+        if name == "a" {
+            context.in_link = true;
+        }
+
+        let role = element.attr("role").map(|role| role.trim().to_ascii_lowercase());
+        let negative_role = role.as_deref().is_some_and(|role| NEGATIVE_ROLES.contains(&role));
+        let positive_role = matches!(role.as_deref(), Some("main" | "article"));
+        // A header inside the content holds its title and byline
+        let page_header = name == "header" && !self.positive;
+        if NEGATIVE_TAGS.contains(&name) || page_header || negative_role {
+            context.negative = true;
+        } else if matches!(name, "article" | "main") || positive_role
+            || element.attr("itemprop").is_some_and(|prop| prop.contains("articleBody"))
+        {
+            context.positive = true;
+        }
+
+        // The hints of the root elements say nothing about a single block
+        if !matches!(name, "html" | "body") {
+            let hints = format!("{} {}", element.attr("class").unwrap_or(""), element.attr("id").unwrap_or(""));
+            if !hints.trim().is_empty() {
+                match (NEGATIVE_HINT.is_match(&hints), POSITIVE_HINT.is_match(&hints)) {
+                    (true, false) => context.negative = true,
+                    (false, true) => context.positive = true,
+                    _ => {}
+                }
+            }
+        }
+        context
+    }
+}
+
+#[derive(Default)]
+struct Walker {
+    blocks: Vec<Block>,
+    text: String,
+    link_chars: usize,
+    /// Whether the last thing seen was a `<br>`
+    after_break: bool,
+}
+
+impl Walker {
+    fn walk(&mut self, element: ElementRef, context: Context, depth: usize) {
+        if depth >= MAX_ELEMENT_DEPTH {
+            return;
+        }
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => {
+                    if !text.trim().is_empty() {
+                        self.after_break = false;
+                    }
+                    if context.in_link {
+                        self.link_chars += text.chars().filter(|c| !c.is_whitespace()).count();
+                    }
+                    self.text.push_str(text);
+                }
+                Node::Element(el) => {
+                    let Some(child_element) = ElementRef::wrap(child) else {
+                        continue;
+                    };
+                    let name = el.name();
+                    if SKIPPED_TAGS.contains(&name) || is_hidden(el) {
+                        continue;
+                    }
+                    // Old pages separate paragraphs with `<br><br>` instead of `<p>`
+                    if name == "br" {
+                        if self.after_break {
+                            self.flush(context, None, false);
+                        } else {
+                            self.text.push(' ');
+                            self.after_break = true;
+                        }
+                        continue;
+                    }
+
+                    let child_context = context.enter(el);
+                    if BLOCK_TAGS.contains(&name) {
+                        self.flush(context, None, false);
+                        self.walk(child_element, child_context, depth + 1);
+                        let heading = name.strip_prefix('h').and_then(|level| level.parse().ok());
+                        let mut block_context = child_context;
+                        block_context.in_link = false;
+                        self.flush(block_context, heading, PROSE_TAGS.contains(&name));
+                    } else {
+                        self.walk(child_element, child_context, depth + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Ends the block collected so far, if it has any text.
+    fn flush(&mut self, context: Context, heading: Option<u8>, prose: bool) {
+        let text = self.text
+            .split_whitespace()
+            .map(|word| word.chars().filter(|c| !c.is_control()).collect::<String>())
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let link_chars = std::mem::take(&mut self.link_chars);
+        self.text.clear();
+        if text.is_empty() {
+            return;
+        }
+
+        let chars = text.chars().filter(|c| !c.is_whitespace()).count();
+        self.blocks.push(Block {
+            words: count_words(&text),
+            text,
+            chars,
+            link_chars: link_chars.min(chars),
+            heading,
+            prose,
+            context,
+        });
+    }
+}
+
+fn is_hidden(element: &Element) -> bool {
+    if element.attr("hidden").is_some() || element.attr("aria-hidden").is_some_and(|v| v.trim() == "true") {
+        return true;
+    }
+    element.attr("style").is_some_and(|style| {
+        let style: String = style.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase();
+        style.contains("display:none") || style.contains("visibility:hidden")
+    })
+}
@@ -0,0 +1,140 @@
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use lazy_static::lazy_static;
+use regex::bytes::Regex;
+
+/// MIME types the crawler knows how to extract content from.
+const SUPPORTED_MIME_TYPES: &[&str] = &["text/html", "application/xhtml+xml", "application/pdf", "text/plain"];
+
+/// How many leading bytes are inspected when sniffing a body.
+pub const SNIFF_LEN: usize = 512;
+
+/// How far into an HTML document a `<meta charset>` declaration is looked
+/// for. Browsers give up after the first 1024 bytes as well.
+const META_CHARSET_SCAN_LEN: usize = 1024;
+
+lazy_static! {
+    // Matches both `<meta charset="x">` and
+    // `<meta http-equiv="Content-Type" content="text/html; charset=x">`
+    static ref META_CHARSET: Regex =
+        Regex::new(r#"(?i)<meta\s[^>]*charset\s*=\s*["']?\s*([a-z0-9_:.\-]+)"#).unwrap();
+}
+
+/// A parsed `Content-Type` header value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    /// Lowercased `type/subtype`, without parameters
+    pub mime_type: String,
+    /// Lowercased `charset` parameter, if any
+    pub charset: Option<String>,
+}
+
+impl ContentType {
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut parts = header.split(';');
+        let mime_type = parts.next()?.trim().to_lowercase();
+        if mime_type.is_empty() || !mime_type.contains('/') {
+            return None;
+        }
+
+        let charset = parts
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+            .map(|(_, value)| value.trim().trim_matches(|c| c == '"' || c == '\'').to_lowercase())
+            .filter(|value| !value.is_empty());
+
+        Some(ContentType { mime_type, charset })
+    }
+}
+
+pub fn is_supported(mime_type: &str) -> bool {
+    SUPPORTED_MIME_TYPES.contains(&mime_type)
+}
+
+/// Picks the encoding of a body: a byte order mark wins, then the charset of
+/// the `Content-Type` header, then (for HTML) a `<meta charset>` declaration.
+/// Without any of them, UTF-8 is assumed if the body is valid UTF-8 and
+/// windows-1252, the web's legacy default, otherwise.
+pub fn detect_encoding(body: &[u8], mime_type: Option<&str>, header_charset: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return encoding;
+    }
+    if let Some(encoding) = header_charset.and_then(|charset| Encoding::for_label(charset.as_bytes())) {
+        return encoding;
+    }
+    if matches!(mime_type, None | Some("text/html") | Some("application/xhtml+xml")) {
+        if let Some(encoding) = meta_charset(body) {
+            return encoding;
+        }
+    }
+
+    if std::str::from_utf8(body).is_ok() {
+        UTF_8
+    } else {
+        WINDOWS_1252
+    }
+}
+
+/// Decodes a body to text with the encoding picked by [`detect_encoding`].
+/// Malformed sequences become U+FFFD.
+pub fn decode(body: &[u8], mime_type: Option<&str>, header_charset: Option<&str>) -> String {
+    let encoding = detect_encoding(body, mime_type, header_charset);
+    encoding.decode_with_bom_removal(body).0.into_owned()
+}
+
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = &body[..body.len().min(META_CHARSET_SCAN_LEN)];
+    let label = META_CHARSET.captures(head)?.get(1)?.as_bytes();
+    // A page cannot declare itself UTF-16 in ASCII-compatible markup; the
+    // HTML standard reads such declarations as UTF-8
+    Encoding::for_label(label).map(Encoding::output_encoding)
+}
+
+/// Guesses the MIME type of a body that came without a `Content-Type`
+/// header, from its first bytes. Text that is not recognized as markup is
+/// reported as `text/plain`, anything else as `application/octet-stream`.
+pub fn sniff(body: &[u8]) -> &'static str {
+    let head = &body[..body.len().min(SNIFF_LEN)];
+
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"%PDF-", "application/pdf"),
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"%!PS", "application/postscript"),
+    ];
+    if let Some((_, mime_type)) = SIGNATURES.iter().find(|(signature, _)| head.starts_with(signature)) {
+        return mime_type;
+    }
+    if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        return "image/webp";
+    }
+
+    let text = head.strip_prefix(b"\xef\xbb\xbf").unwrap_or(head);
+    let start = text.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(text.len());
+    let lower = text[start..].to_ascii_lowercase();
+    const HTML_PREFIXES: &[&[u8]] = &[
+        b"<!doctype html", b"<html", b"<head", b"<body", b"<script", b"<iframe",
+        b"<h1", b"<div", b"<font", b"<table", b"<a", b"<style", b"<title", b"<b", b"<br", b"<p",
+    ];
+    // A tag name has to end here, so that e.g. "<a" does not match "<abbr"
+    if lower.starts_with(b"<!--") || HTML_PREFIXES.iter().any(|prefix| {
+        lower.starts_with(prefix)
+            && lower.get(prefix.len()).is_some_and(|b| b.is_ascii_whitespace() || *b == b'>')
+    }) {
+        return "text/html";
+    }
+    if lower.starts_with(b"<?xml") {
+        return "text/xml";
+    }
+
+    // Control characters other than whitespace only show up in binary data
+    let is_binary = head.iter().any(|b| matches!(b, 0x00..=0x08 | 0x0b | 0x0e..=0x1a | 0x1c..=0x1f));
+    if is_binary {
+        "application/octet-stream"
+    } else {
+        "text/plain"
+    }
+}
@@ -1,24 +1,113 @@
-use crate::fetcher;
+use crate::fetcher::{self, FetchRequest, FetchedPage};
 use crate::error::CrawlerError;
 use crate::config::Config;
-use crate::parser;
+use crate::parser::{self, RobotsDirectives};
+use crate::document_date;
 use crate::metrics;
 use crate::summarizer;
-use crate::robots::RobotsChecker;
+use crate::robots::{RobotsChecker, RobotsVerdict};
+use crate::scheduler::{HostScheduler, HostPoliteness};
+use crate::revisit::RevisitPolicy;
+use crate::scope::CrawlScope;
+use crate::canonicalizer::{self, UrlCanonicalizer};
+use crate::network::NetworkPolicy;
+use crate::quality::{PageSignals, QualityScorer, WeightedQualityScorer};
+use crate::sitemap;
+use crate::simhash;
 use log::{info, warn, error, debug};
 use std::collections::HashSet;
 use std::error::Error;
+use std::future::Future;
+use futures::future::BoxFuture;
+use futures::stream::{FuturesUnordered, StreamExt};
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::Mutex;
-use std::collections::VecDeque;
 use reqwest::Client;
 use url::Url;
 use uuid::Uuid;
-use storage::{PostgresStorage, Webpage, Link};
+use storage::postgre::StorageError;
+use storage::{PostgresStorage, Webpage, Link, SavedWebpage, JobCounters, JobStatus, FetchLogEntry, FrontierBacklog};
 use tokio::time::Duration;
 use std::collections::HashMap;
-use urlencoding;
+use chrono::{DateTime, Utc};
+
+/// Frontier of the continuous crawl from the configured seeds. It outlives
+/// individual jobs, so a restarted crawler resumes where the last one stopped.
+/// Every other job crawls its own frontier, keyed by the job id.
+const SEED_FRONTIER: Uuid = Uuid::nil();
+
+/// How long a leased frontier entry stays reserved before another crawler
+/// (or this one, after a restart) may pick it up again.
+const FRONTIER_LEASE_SECS: f64 = 600.0;
+
+/// How often the crawl extends the leases of the URLs it holds. URLs of a host
+/// with a long crawl delay may wait in the scheduler for longer than a lease.
+const LEASE_RENEW_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Frontier priorities. Higher values are leased first.
+const PRIORITY_DEFAULT: i32 = 0;
+const PRIORITY_DOMAIN: i32 = 10;
+const PRIORITY_SEED: i32 = 20;
+
+/// Depth at which URLs found in a host's sitemaps enter the frontier.
+const SITEMAP_DEPTH: i32 = 1;
+
+/// How many stored pages matching a job's query it starts from.
+const QUERY_DISCOVERY_LIMIT: i64 = 50;
+
+/// How many URLs per concurrent request slot are kept leased in the host
+/// scheduler, so that a batch can be spread over several ready hosts.
+const SCHEDULER_BUFFER_FACTOR: usize = 4;
+
+/// How long a URL waits at least before being retried when its host's
+/// robots.txt could not be retrieved. Like failed fetches, such URLs are given
+/// up after `MAX_FETCH_ATTEMPTS`.
+const ROBOTS_RETRY_DELAY: Duration = Duration::from_secs(900);
+
+/// A frontier URL failing temporarily is given up after this many fetches.
+const MAX_FETCH_ATTEMPTS: i32 = 5;
+
+/// Delay before the first retry of a temporary fetch failure. It doubles with
+/// every further attempt, unless the server's `Retry-After` asks for longer.
+const RETRY_BASE_DELAY_SECS: f64 = 60.0;
+
+/// Upper bound for retry delays, including ones asked for by `Retry-After`.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(6 * 3600);
+
+/// Longest a `Retry-After` holds back the host in the scheduler. URLs of the
+/// host leased meanwhile are fetched after that; the failed URL itself waits
+/// for the full delay in storage.
+const MAX_HOST_PAUSE: Duration = Duration::from_secs(300);
+
+/// Longest the crawl sleeps at a time while it waits for deferred frontier
+/// entries or a host to become ready, so that a cancelled job still stops
+/// promptly and its leases are renewed in time.
+const MAX_FRONTIER_WAIT: Duration = Duration::from_secs(30);
+
+/// How many near-duplicates are looked at when clustering a stored page.
+const DUPLICATE_CANDIDATES: i64 = 10;
+
+/// How often a crawler reports that it is still working on its jobs.
+const JOB_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Jobs whose crawler has not reported in for this long are taken for
+/// interrupted and failed.
+const JOB_STALE_AFTER: Duration = Duration::from_secs(180);
+
+/// Where a scheduled URL was leased from, so its outcome is recorded in the
+/// right place.
+#[derive(Debug, Clone, Copy)]
+enum LeaseSource {
+    /// A newly discovered URL from the frontier, fetched `attempts` times before
+    Frontier { id: Uuid, attempts: i32 },
+    /// A due revisit of the stored page with this id
+    Revisit(Uuid),
+}
+
+/// A leased URL: its canonical form, the URL to request when that differs,
+/// its depth and where it was leased from.
+type LeasedUrl = (String, Option<String>, usize, LeaseSource);
 
 fn display_crawl_summary(visited_urls: &HashSet<String>, queue_size: usize) {
     println!("\nCrawler Status Summary:");
@@ -28,6 +117,20 @@ fn display_crawl_summary(visited_urls: &HashSet<String>, queue_size: usize) {
     println!("----------------------\n");
 }
 
+/// What a crawl job crawls and how far it goes.
+#[derive(Debug, Clone)]
+pub struct CrawlJobSpec {
+    /// URLs the job starts from
+    pub seed_urls: Vec<String>,
+    /// Also start from the stored pages matching this query, and the pages
+    /// they link to
+    pub query: Option<String>,
+    /// URLs the job may visit, on top of the crawler-wide domain filters
+    pub scope: CrawlScope,
+    pub max_depth: usize,
+    pub max_pages: usize,
+}
+
 #[derive(Debug)]
 pub struct CrawlStatus {
     pub has_results: bool,
@@ -36,30 +139,70 @@ pub struct CrawlStatus {
     pub message: String,
 }
 
+/// Crawls pages for any number of concurrently running jobs.
+///
+/// All shared state is synchronized internally, so a single `Crawler` can be
+/// shared behind an `Arc` and used by several jobs at once. Jobs share the
+/// HTTP client, the robots.txt cache and per-host politeness; frontier and
+/// visited set are per job.
 pub struct Crawler {
     robots_checker: RobotsChecker,
+    politeness: Arc<HostPoliteness>,
     client: Client,
+    /// Client for pages, whose redirects the fetcher follows itself
+    page_client: Client,
+    network: Arc<NetworkPolicy>,
     config: Arc<Config>,
     storage: PostgresStorage,
-    queue: Arc<Mutex<VecDeque<(String, usize)>>>,
-    visited: Arc<Mutex<HashSet<String>>>,
-    crawl_start_time: Arc<Mutex<Option<Instant>>>,
+    lease_owner: Uuid,
+    revisit_policy: RevisitPolicy,
+    canonicalizer: UrlCanonicalizer,
+    quality_scorer: Box<dyn QualityScorer>,
     initialized: Arc<Mutex<bool>>,
-    query_cache: Arc<Mutex<HashMap<String, HashSet<String>>>>,
 }
 
 impl Crawler {
-    pub fn new(client: Client, config: Config, storage: PostgresStorage) -> Self {
+    pub fn new(client: Client, page_client: Client, network: Arc<NetworkPolicy>, config: Config, storage: PostgresStorage) -> Self {
         Crawler {
-            robots_checker: RobotsChecker::new(client.clone()),
+            robots_checker: RobotsChecker::new(client.clone(), network.clone()),
+            politeness: Arc::new(HostPoliteness::new(Duration::from_millis(config.request_delay))),
             client,
+            page_client,
+            network,
+            revisit_policy: RevisitPolicy::from_config(&config),
+            canonicalizer: UrlCanonicalizer::new(&config.strip_query_params),
+            quality_scorer: Box::new(WeightedQualityScorer::from_config(&config)),
             config: Arc::new(config),
             storage,
-            queue: Arc::new(Mutex::new(VecDeque::new())),
-            visited: Arc::new(Mutex::new(HashSet::new())),
-            crawl_start_time: Arc::new(Mutex::new(None)),
+            lease_owner: Uuid::new_v4(),
             initialized: Arc::new(Mutex::new(false)),
-            query_cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Identifies this crawler process as the owner of its jobs and leases.
+    pub fn instance_id(&self) -> Uuid {
+        self.lease_owner
+    }
+
+    /// Keeps the jobs of this crawler marked as alive and fails the jobs of
+    /// crawlers that stopped, e.g. a previous run of this one. Runs forever.
+    pub async fn watch_jobs(&self) {
+        let mut interval = tokio::time::interval(JOB_HEARTBEAT_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.storage.heartbeat_crawl_jobs(self.lease_owner).await {
+                warn!("Failed to record heartbeat of running jobs: {}", e);
+            }
+            match self.storage.fail_interrupted_crawl_jobs(JOB_STALE_AFTER.as_secs_f64()).await {
+                Ok(0) => {}
+                Ok(interrupted) => {
+                    info!("Marked {} interrupted crawl jobs as failed", interrupted);
+                    if let Err(e) = self.storage.purge_finished_job_frontiers().await {
+                        warn!("Failed to remove frontiers of interrupted jobs: {}", e);
+                    }
+                }
+                Err(e) => warn!("Failed to fail interrupted crawl jobs: {}", e),
+            }
         }
     }
 
@@ -72,99 +215,154 @@ impl Crawler {
 
         info!("Initializing crawler with seed URLs...");
         for seed_url in &self.config.seed_urls {
-            let normalized_url = normalize_url(seed_url)?;
-            info!("Adding seed URL to queue: {}", normalized_url);
-            self.queue.lock().await.push_back((normalized_url, 0));
+            let (normalized_url, fetch_url) = self.frontier_url(seed_url)?;
+            if self.storage.enqueue_url(SEED_FRONTIER, &normalized_url, fetch_url.as_deref(), 0, PRIORITY_DEFAULT).await? {
+                info!("Added seed URL to frontier: {}", normalized_url);
+            } else {
+                debug!("Seed URL already in frontier: {}", normalized_url);
+            }
         }
 
         *initialized = true;
         Ok(())
     }
 
-    pub async fn crawl_for_query(&self, query: &str, max_depth: usize, max_pages: usize) -> Result<(), Box<dyn Error + Send + Sync>> {
-        self.initialize().await?;
+    /// Runs the continuous crawl from the configured seeds as job `job_id`.
+    pub async fn run_seed_crawl(&self, job_id: Uuid) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let spec = CrawlJobSpec {
+            seed_urls: self.config.seed_urls.clone(),
+            query: None,
+            scope: CrawlScope::unrestricted(),
+            max_depth: self.config.max_depth,
+            max_pages: self.config.max_pages,
+        };
+
+        self.track_job(job_id, async {
+            self.initialize().await?;
+            self.crawl_with_params(job_id, SEED_FRONTIER, &spec).await
+        }).await
+    }
+
+    /// Runs job `job_id` in its own frontier, starting from the spec's seed
+    /// URLs and the stored pages that match its query.
+    pub async fn run_job(&self, job_id: Uuid, spec: CrawlJobSpec) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let result = self.track_job(job_id, async {
+            self.seed_job_frontier(job_id, &spec).await?;
+            self.crawl_with_params(job_id, job_id, &spec).await
+        }).await;
 
-        // Reset visited set for new query
-        {
-            let mut visited = self.visited.lock().await;
-            visited.clear();
+        if let Err(e) = self.storage.delete_job_frontier(job_id).await {
+            warn!("Failed to delete frontier of job {}: {}", job_id, e);
+        }
+        result
+    }
+
+    /// Runs `crawl` as the queued job `job_id` and records its state
+    /// transitions. A job cancelled before it starts is skipped.
+    async fn track_job<F>(&self, job_id: Uuid, crawl: F) -> Result<(), Box<dyn Error + Send + Sync>>
+    where
+        F: Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+    {
+        if !self.storage.start_crawl_job(job_id).await? {
+            info!("Job {} is no longer queued, skipping it", job_id);
+            return Ok(());
         }
+        info!("Starting job {}", job_id);
 
-        let cache_hit = {
-            let cache = self.query_cache.lock().await;
-            cache.get(query).cloned()
+        let result = crawl.await;
+
+        let finished = match &result {
+            Ok(()) => self.storage.finish_crawl_job(job_id, JobStatus::Completed, None).await,
+            Err(e) => self.storage.finish_crawl_job(job_id, JobStatus::Failed, Some(&e.to_string())).await,
         };
+        if let Err(e) = finished {
+            warn!("Failed to record the end of job {}: {}", job_id, e);
+        }
+        info!("Job {} finished", job_id);
+        result
+    }
 
-        if let Some(cached_urls) = cache_hit {
-            info!("Found {} cached URLs for query: {}", cached_urls.len(), query);
-            let mut queue = self.queue.lock().await;
-            queue.clear(); // Clear existing queue
-            for url in cached_urls {
-                queue.push_back((url, 0));
-            }
-        } else {
-            let relevant_urls = self.find_relevant_urls(query).await?;
-            let mut cache = self.query_cache.lock().await;
-            cache.insert(query.to_string(), relevant_urls.clone());
-            
-            let mut queue = self.queue.lock().await;
-            queue.clear(); // Clear existing queue
-            for url in relevant_urls {
-                queue.push_back((url, 0));
+    /// Fills the frontier of a new job with its seed URLs and, for a query,
+    /// with the stored pages matching it and the pages they link to.
+    async fn seed_job_frontier(&self, job_id: Uuid, spec: &CrawlJobSpec) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut seeds: Vec<(String, Option<String>, i32)> = Vec::new();
+        for url in &spec.seed_urls {
+            match self.frontier_url(url) {
+                Ok((normalized, fetch_url)) => seeds.push((normalized, fetch_url, 0)),
+                Err(e) => warn!("Ignoring seed URL {} of job {}: {}", url, job_id, e),
             }
         }
 
-        self.crawl_with_params(max_depth, max_pages).await
-    }
-
-    async fn find_relevant_urls(&self, query: &str) -> Result<HashSet<String>, Box<dyn Error + Send + Sync>> {
-        let mut relevant_urls = HashSet::new();
-        let search_domains = vec![
-            "stackoverflow.com",
-            "github.com",
-            "medium.com",
-            "dev.to",
-            "reddit.com/r/programming",
-        ];
+        if let Some(query) = &spec.query {
+            let pages = self.storage.search_webpages(query, QUERY_DISCOVERY_LIMIT).await?;
+            info!("Found {} stored pages matching query '{}'", pages.len(), query);
+            let page_ids: Vec<Uuid> = pages.iter().map(|page| page.id).collect();
+            seeds.extend(pages.into_iter().map(|page| (page.url, None, 0)));
 
-        for domain in search_domains {
-            let url = format!("https://{}/search?q={}", domain, urlencoding::encode(query));
-            relevant_urls.insert(url);
+            if spec.max_depth > 0 {
+                for target in self.storage.get_link_targets(&page_ids).await? {
+                    if let Ok((normalized, fetch_url)) = self.frontier_url(&target) {
+                        seeds.push((normalized, fetch_url, 1));
+                    }
+                }
+            }
         }
 
-        Ok(relevant_urls)
+        let mut enqueued = 0;
+        for (url, fetch_url, depth) in seeds {
+            if !spec.scope.allows(&url) || !self.config.should_follow_link(&url, "") {
+                debug!("Seed {} is outside the scope of job {}", url, job_id);
+                continue;
+            }
+            if self.storage.enqueue_url(job_id, &url, fetch_url.as_deref(), depth, PRIORITY_SEED).await? {
+                enqueued += 1;
+            }
+        }
+        info!("Job {} starts from {} URLs", job_id, enqueued);
+        Ok(())
     }
 
-    /// Crawl with specific parameters
-    pub async fn crawl_with_params(&self, max_depth: usize, max_pages: usize) -> Result<(), Box<dyn Error + Send + Sync>> {
-        *self.crawl_start_time.lock().await = Some(Instant::now());
+    /// Crawl `frontier` within the limits and scope of `spec`, recording
+    /// progress on the job `job_id`. Stops early once the job is cancelled.
+    pub async fn crawl_with_params(&self, job_id: Uuid, frontier: Uuid, spec: &CrawlJobSpec) -> Result<(), Box<dyn Error + Send + Sync>> {
         info!("Starting crawl process");
+        let max_depth = spec.max_depth;
+        let max_pages = spec.max_pages;
         
+        let mut visited = HashSet::new();
+        let mut sitemap_hosts = HashSet::new();
         let mut pages_crawled = 0;
-        let mut _errors = 0;  // Using _errors to indicate intentionally unused
+        let mut counters = JobCounters::default();
         let start_time = Instant::now();
         metrics::set_queue_size(0);
         
+        let mut scheduler = HostScheduler::new(self.politeness.clone());
+        // Sitemap discoveries run alongside the crawl, so that slow sitemaps
+        // and long crawl delays do not hold up the other hosts
+        let mut discoveries: FuturesUnordered<BoxFuture<'_, ()>> = FuturesUnordered::new();
+        let mut lease_sources: HashMap<String, LeaseSource> = HashMap::new();
+        // URLs to request for leased URLs whose canonical form differs
+        let mut fetch_urls: HashMap<String, String> = HashMap::new();
+        let mut leases_renewed_at = Instant::now();
+        let max_page_rank = match self.storage.max_page_rank().await {
+            Ok(rank) => rank,
+            Err(e) => {
+                warn!("Failed to load maximum page rank, ignoring rank for revisits: {}", e);
+                0.0
+            }
+        };
+
         info!("Starting main crawl loop");
         loop {
-            let mut batch = Vec::new();
-            {
-                let mut queue = self.queue.lock().await;
-                while batch.len() < self.config.concurrent_requests && !queue.is_empty() {
-                    if let Some((url, depth)) = queue.pop_front() {
-                        if !self.visited.lock().await.contains(&url) {
-                            if depth <= max_depth {
-                                batch.push((url, depth));
-                            }
-                        }
-                    }
-                }
-                // Update queue size metric
-                metrics::set_queue_size(queue.len() as i64);
+            if self.is_job_cancelled(job_id).await {
+                info!("Job {} was cancelled, stopping crawl", job_id);
+                self.release_leases(&mut scheduler, &mut lease_sources).await?;
+                break;
             }
 
-            if batch.is_empty() {
-                info!("Queue is empty, crawling complete");
+            if pages_crawled >= max_pages {
+                info!("Reached maximum pages limit of {}", max_pages);
+                self.release_leases(&mut scheduler, &mut lease_sources).await?;
                 // Record cycle completion
                 metrics::increment_crawl_cycles();
                 let duration = start_time.elapsed().as_secs_f64();
@@ -172,12 +370,89 @@ impl Crawler {
                 break;
             }
 
-            let queue_size = self.queue.lock().await.len();
-            let visited_count = pages_crawled;
-            info!("Current queue size: {}, visited count: {}", queue_size, visited_count);
+            if leases_renewed_at.elapsed() >= LEASE_RENEW_INTERVAL {
+                self.renew_leases(&lease_sources).await;
+                leases_renewed_at = Instant::now();
+            }
 
-            if visited_count >= max_pages {
-                info!("Reached maximum pages limit of {}", max_pages);
+            let buffer_target = self.config.concurrent_requests * SCHEDULER_BUFFER_FACTOR;
+            if scheduler.len() < buffer_target {
+                let leased = self.lease_work(frontier, buffer_target - scheduler.len(), max_depth).await?;
+                for (url, fetch_url, depth, source) in leased {
+                    // Robots rules are applied before a URL is scheduled, so disallowed
+                    // pages are never requested
+                    let request_url = fetch_url.as_deref().unwrap_or(&url);
+                    let (verdict, crawl_delay) = self.robots_checker.check(request_url, &self.config.user_agent).await;
+                    match verdict {
+                        RobotsVerdict::Allowed => {}
+                        RobotsVerdict::Disallowed => {
+                            info!("URL {} is disallowed by robots.txt", url);
+                            counters.pages_skipped += 1;
+                            self.log_fetch(FetchLogEntry {
+                                url: url.clone(),
+                                error_kind: Some("robots_disallowed".to_string()),
+                                ..Default::default()
+                            }).await;
+                            let updated = match source {
+                                LeaseSource::Frontier { id, .. } => self.storage.mark_frontier_failed(id, "disallowed by robots.txt").await,
+                                LeaseSource::Revisit(id) => self.storage.schedule_revisit(id, Utc::now() + self.revisit_policy.max_interval()).await,
+                            };
+                            if let Err(e) = updated {
+                                warn!("Failed to update lease for {}: {}", url, e);
+                            }
+                            continue;
+                        }
+                        RobotsVerdict::Unreachable => {
+                            self.log_fetch(FetchLogEntry {
+                                url: url.clone(),
+                                error_kind: Some("robots_unreachable".to_string()),
+                                ..Default::default()
+                            }).await;
+                            self.retry_later(&url, source, "robots.txt unreachable", Some(ROBOTS_RETRY_DELAY)).await;
+                            continue;
+                        }
+                    }
+
+                    let host = HostScheduler::host_key(&url);
+                    if self.politeness.set_crawl_delay(&host, crawl_delay) {
+                        if let Some(delay) = crawl_delay {
+                            info!("Using crawl delay of {:?} for {}", delay, host);
+                        }
+                    }
+                    if self.config.use_sitemaps && sitemap_hosts.insert(host.clone()) {
+                        discoveries.push(Box::pin(self.enqueue_sitemap_urls(frontier, spec, url.clone(), host)));
+                    }
+                    lease_sources.insert(url.clone(), source);
+                    if let Some(fetch_url) = fetch_url {
+                        fetch_urls.insert(url.clone(), fetch_url);
+                    }
+                    scheduler.push(url, depth);
+                }
+            }
+
+            let backlog = self.storage.count_pending_frontier(frontier, max_depth as i32).await?;
+            let queue_size = backlog.total() as usize;
+            metrics::set_queue_size(queue_size as i64);
+            counters.urls_deferred = backlog.deferred as i32;
+
+            if scheduler.is_empty() {
+                // URLs deferred for a retry are still part of the crawl
+                match time_until_leasable(&backlog) {
+                    Some(wait) if wait.is_zero() => continue,
+                    Some(wait) => {
+                        info!("Waiting {:?} for {} deferred URLs", wait, backlog.deferred);
+                        self.record_job_progress(job_id, &counters).await;
+                        while_discovering(&mut discoveries, tokio::time::sleep(wait)).await;
+                        continue;
+                    }
+                    None if !discoveries.is_empty() => {
+                        // Sitemaps still being read may add URLs
+                        discoveries.next().await;
+                        continue;
+                    }
+                    None => {}
+                }
+                info!("Queue is empty, crawling complete");
                 // Record cycle completion
                 metrics::increment_crawl_cycles();
                 let duration = start_time.elapsed().as_secs_f64();
@@ -185,64 +460,486 @@ impl Crawler {
                 break;
             }
 
-            display_crawl_summary(&self.visited.lock().await.clone(), queue_size);
+            let batch = scheduler.next_batch(self.config.concurrent_requests);
+            if batch.is_empty() {
+                // Every buffered host is still inside its politeness interval
+                if let Some(wait) = scheduler.next_ready_in().map(|wait| wait.min(MAX_FRONTIER_WAIT)) {
+                    debug!("No host ready, waiting {:?}", wait);
+                    while_discovering(&mut discoveries, tokio::time::sleep(wait)).await;
+                }
+                continue;
+            }
+
+            info!("Current queue size: {}, visited count: {}", queue_size, pages_crawled);
+
+            display_crawl_summary(&visited, queue_size);
 
-            let results = fetcher::fetch_pages_in_parallel(
-                batch,
-                &self.client,
-                self.config.request_delay,
+            let batch_urls: Vec<String> = batch.iter().map(|(url, _)| url.clone()).collect();
+            let mut validators = match self.storage.get_cache_validators(&batch_urls).await {
+                Ok(validators) => validators,
+                Err(e) => {
+                    warn!("Failed to load cache validators, fetching unconditionally: {}", e);
+                    HashMap::new()
+                }
+            };
+            let sitemap_lastmods = match self.storage.get_sitemap_lastmods(&batch_urls).await {
+                Ok(lastmods) => lastmods,
+                Err(e) => {
+                    warn!("Failed to load sitemap lastmods: {}", e);
+                    HashMap::new()
+                }
+            };
+
+            let requests = batch.into_iter()
+                .map(|(url, depth)| FetchRequest {
+                    fetch_url: fetch_urls.get(&url).cloned(),
+                    validators: validators.remove(&url),
+                    url,
+                    depth,
+                })
+                .collect();
+            let results = while_discovering(&mut discoveries, fetcher::fetch_pages_in_parallel(
+                requests,
+                &self.page_client,
+                &self.network,
                 self.config.concurrent_requests,
                 &self.config.user_agent,
                 self.config.max_content_size,
-            ).await;
+            )).await;
 
-            for (url, depth, result) in results {
-                match result {
-                    Ok((content, status)) => {
-                        let allowed = self.robots_checker.is_allowed(&url, &self.config.user_agent).await;
-                        if !allowed {
-                            info!("URL {} is disallowed by robots.txt", url);
-                            continue;
-                        }
+            for fetched in results {
+                fetch_urls.remove(&fetched.url);
+                let Some(source) = lease_sources.remove(&fetched.url) else {
+                    continue;
+                };
+                self.log_fetch(fetch_log_entry(&fetched)).await;
+
+                // Pages are stored under the URL they were finally served from
+                let final_url = self.canonicalizer.canonicalize(fetched.final_url())
+                    .unwrap_or_else(|_| fetched.final_url().to_string());
+                let fetcher::FetchOutcome { url, depth, elapsed, redirects, result } = fetched;
+                let redirected = final_url != url;
 
+                // Slow and failing hosts are asked less often
+                let host = HostScheduler::host_key(&url);
+                let failed = result.as_ref().err().is_some_and(CrawlerError::is_retryable);
+                let interval = self.politeness.record_response(&host, elapsed, failed);
+                if failed {
+                    debug!("{} failed temporarily, request interval now {:?}", host, interval);
+                }
+                if let Some(retry_after) = result.as_ref().err().and_then(CrawlerError::retry_after) {
+                    self.politeness.pause(&host, retry_after.min(MAX_HOST_PAUSE));
+                }
+
+                let not_modified = result.as_ref().is_ok_and(FetchedPage::is_not_modified);
+                let moved_host = matches!(result, Err(CrawlerError::CrossHostRedirect(_)));
+                let outcome = match result {
+                    Ok(_) | Err(CrawlerError::CrossHostRedirect(_)) if redirected && !spec.scope.allows(&final_url) => {
+                        debug!("Skipping {}: redirected outside the job's scope to {}", url, final_url);
+                        counters.pages_skipped += 1;
+                        Err(format!("redirected outside the job's scope to {}", final_url))
+                    }
+                    Ok(_) if not_modified => {
+                        visited.insert(url.clone());
+                        pages_crawled += 1;
+                        counters.pages_fetched += 1;
+                        metrics::increment_pages_crawled();
+                        // The validators, and so the page, may be those of the URL it is stored under
+                        self.storage.touch_webpage(&url).await.map_err(|e| e.to_string())
+                    }
+                    Ok(page) => {
                         info!("Successfully fetched {}", url);
-                        self.visited.lock().await.insert(url.clone());
+                        visited.insert(url.clone());
+                        visited.insert(final_url.clone());
                         pages_crawled += 1;
+                        counters.pages_fetched += 1;
                         metrics::increment_pages_crawled();
-                        
-                        if let Err(e) = self.process_page(&url, &content, status.as_u16() as i32, depth).await {
-                            error!("Error processing page {}: {}", url, e);
-                            metrics::increment_crawl_errors();
-                            continue;
+
+                        match self.process_page(frontier, spec, &final_url, &page, depth, sitemap_lastmods.get(&url).copied()).await {
+                            Ok(Some(saved)) => {
+                                counters.pages_stored += 1;
+                                Ok(Some(saved))
+                            }
+                            Ok(None) => {
+                                counters.pages_skipped += 1;
+                                Ok(None)
+                            }
+                            Err(e) => {
+                                error!("Error processing page {}: {}", url, e);
+                                counters.errors += 1;
+                                metrics::increment_crawl_errors();
+                                Err(e.to_string())
+                            }
+                        }
+                    }
+                    Err(CrawlerError::CrossHostRedirect(target)) => {
+                        // The target is fetched in its own turn, once its host's
+                        // robots.txt allows it and its interval has passed
+                        match self.enqueue_redirect_target(frontier, spec, &target, depth).await {
+                            Ok(true) => {
+                                info!("{} redirects to another host, queued {}", url, target);
+                                Ok(None)
+                            }
+                            Ok(false) => {
+                                debug!("Skipping {}: redirects to {}, which is not followed", url, target);
+                                counters.pages_skipped += 1;
+                                Err(format!("redirects to {}, which is not followed", target))
+                            }
+                            Err(e) => Err(e),
                         }
                     }
+                    Err(CrawlerError::UnsupportedContentType(mime_type)) => {
+                        // Not a failure of the crawl, just nothing we can index
+                        debug!("Skipping {}: unsupported content type {}", url, mime_type);
+                        counters.pages_skipped += 1;
+                        Err(format!("unsupported content type {}", mime_type))
+                    }
+                    Err(e) if e.is_retryable() => {
+                        warn!("Temporary failure fetching {}: {}", url, e);
+                        counters.errors += 1;
+                        metrics::increment_crawl_errors();
+                        self.retry_later(&url, source, &e.to_string(), e.retry_after()).await;
+                        continue;
+                    }
                     Err(e) => {
                         error!("Error fetching {}: {}", url, e);
+                        counters.errors += 1;
                         metrics::increment_crawl_errors();
+                        Err(e.to_string())
+                    }
+                };
+
+                // Remember where the page of the requested URL is stored, so that
+                // its next fetch can be conditional
+                if let (Ok(saved), false) = (&outcome, not_modified) {
+                    let stored_url = saved.as_ref().map_or(final_url.as_str(), |saved| saved.url.as_str());
+                    let recorded = if stored_url != url {
+                        let status = redirects.status.filter(|_| redirected).map(|status| status.as_u16() as i32);
+                        self.storage.save_redirect(&url, stored_url, status).await
+                    } else {
+                        self.storage.delete_redirect(&url).await
+                    };
+                    if let Err(e) = recorded {
+                        warn!("Failed to record where {} is stored: {}", url, e);
+                    }
+                }
+
+                let next_fetch_at = match &outcome {
+                    Ok(Some(saved)) => Utc::now() + self.revisit_policy.next_interval(&saved.history, max_page_rank),
+                    // A page that moved to another host is checked again rarely
+                    Ok(None) if moved_host => Utc::now() + self.revisit_policy.max_interval(),
+                    // Revisits of pages that could not be fetched or stored are retried later
+                    _ => Utc::now() + self.revisit_policy.min_interval(),
+                };
+                let scheduled = match &outcome {
+                    Ok(Some(saved)) => {
+                        // A stored page that now redirects elsewhere is checked again rarely
+                        let moved = match source {
+                            LeaseSource::Revisit(old_id) if old_id != saved.id => Some(old_id),
+                            _ => None,
+                        };
+                        if let Some(old_id) = moved {
+                            if let Err(e) = self.storage.schedule_revisit(old_id, Utc::now() + self.revisit_policy.max_interval()).await {
+                                warn!("Failed to schedule revisit of {}: {}", url, e);
+                            }
+                        }
+                        self.storage.schedule_revisit(saved.id, next_fetch_at).await
+                    }
+                    _ => match source {
+                        LeaseSource::Revisit(webpage_id) => self.storage.schedule_revisit(webpage_id, next_fetch_at).await,
+                        LeaseSource::Frontier { .. } => Ok(()),
+                    },
+                };
+                if let Err(e) = scheduled {
+                    warn!("Failed to schedule revisit of {}: {}", url, e);
+                }
+
+                if let LeaseSource::Frontier { id: frontier_id, .. } = source {
+                    let marked = match outcome {
+                        Ok(_) => self.storage.mark_frontier_done(frontier_id).await,
+                        Err(reason) => self.storage.mark_frontier_failed(frontier_id, &reason).await,
+                    };
+                    if let Err(e) = marked {
+                        warn!("Failed to update frontier entry for {}: {}", url, e);
                     }
                 }
             }
+
+            self.record_job_progress(job_id, &counters).await;
         }
 
+        self.record_job_progress(job_id, &counters).await;
         Ok(())
     }
 
-    async fn process_page(&self, url: &str, content: &str, status: i32, depth: usize) -> Result<(), Box<dyn Error>> {
-        let parsed_page = parser::parse_webpage(content, url, status)?;
-        
-        let quality_score = self.calculate_crawl_quality_score(&parsed_page);
+    /// Puts a URL that could not be fetched for a temporary reason back in
+    /// line: its frontier entry is deferred, or its revisit rescheduled, for
+    /// at least `retry_after`. Frontier entries are given up after
+    /// `MAX_FETCH_ATTEMPTS`.
+    async fn retry_later(&self, url: &str, source: LeaseSource, reason: &str, retry_after: Option<Duration>) {
+        let updated = match source {
+            LeaseSource::Frontier { id, attempts } if attempts + 1 >= MAX_FETCH_ATTEMPTS => {
+                warn!("Giving up on {} after {} attempts", url, attempts + 1);
+                self.storage.mark_frontier_failed(id, reason).await
+            }
+            LeaseSource::Frontier { id, attempts } => {
+                let delay = retry_delay(retry_after, attempts);
+                info!("Retrying {} in {:?}", url, delay);
+                self.storage.defer_frontier_entry(id, delay.as_secs_f64(), reason).await
+            }
+            LeaseSource::Revisit(webpage_id) => {
+                let delay = retry_delay(retry_after, 0).max(self.revisit_policy.min_interval());
+                self.storage.schedule_revisit(webpage_id, Utc::now() + delay).await
+            }
+        };
+        if let Err(e) = updated {
+            warn!("Failed to reschedule {}: {}", url, e);
+        }
+    }
+
+    async fn log_fetch(&self, entry: FetchLogEntry) {
+        if let Err(e) = self.storage.log_fetch(&entry).await {
+            warn!("Failed to log fetch of {}: {}", entry.url, e);
+        }
+    }
+
+    async fn record_job_progress(&self, job_id: Uuid, counters: &JobCounters) {
+        if let Err(e) = self.storage.update_crawl_job_counters(job_id, counters).await {
+            warn!("Failed to update counters of job {}: {}", job_id, e);
+        }
+    }
+
+    async fn is_job_cancelled(&self, job_id: Uuid) -> bool {
+        match self.storage.get_crawl_job(job_id).await {
+            Ok(Some(job)) => job.status == JobStatus::Cancelled,
+            Ok(None) => false,
+            Err(e) => {
+                warn!("Failed to look up job {}: {}", job_id, e);
+                false
+            }
+        }
+    }
+
+    /// Extends the leases of every URL the crawl holds, whether it waits in the
+    /// scheduler or is being fetched, so that it is not handed out again.
+    async fn renew_leases(&self, lease_sources: &HashMap<String, LeaseSource>) {
+        let mut frontier_ids = Vec::new();
+        let mut revisit_ids = Vec::new();
+        for source in lease_sources.values() {
+            match *source {
+                LeaseSource::Frontier { id, .. } => frontier_ids.push(id),
+                LeaseSource::Revisit(id) => revisit_ids.push(id),
+            }
+        }
+
+        match self.storage.renew_frontier_leases(&frontier_ids, self.lease_owner, FRONTIER_LEASE_SECS).await {
+            Ok(renewed) if (renewed as usize) < frontier_ids.len() => {
+                warn!("{} leases expired before they were renewed", frontier_ids.len() - renewed as usize);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to renew frontier leases: {}", e),
+        }
+        if let Err(e) = self.storage.renew_revisit_leases(&revisit_ids, FRONTIER_LEASE_SECS).await {
+            warn!("Failed to renew revisit leases: {}", e);
+        }
+    }
+
+    /// Hands URLs that were scheduled but not fetched back to storage, so the
+    /// next run can lease them immediately.
+    async fn release_leases(
+        &self,
+        scheduler: &mut HostScheduler,
+        lease_sources: &mut HashMap<String, LeaseSource>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut unfetched_frontier = Vec::new();
+        let mut unfetched_revisits = Vec::new();
+        for (url, _) in scheduler.drain() {
+            match lease_sources.remove(&url) {
+                Some(LeaseSource::Frontier { id, .. }) => unfetched_frontier.push(id),
+                Some(LeaseSource::Revisit(id)) => unfetched_revisits.push(id),
+                None => {}
+            }
+        }
+        self.storage.release_frontier_leases(&unfetched_frontier).await?;
+        self.storage.release_revisit_leases(&unfetched_revisits).await?;
+        Ok(())
+    }
+
+    /// Feeds the URLs listed in the sitemaps of `host` into `frontier`, with
+    /// priorities hinted by the sitemaps. Sitemaps are taken from the host's
+    /// robots.txt, falling back to `/sitemap.xml`. For the seed crawl, stored
+    /// pages whose `lastmod` is newer than their last fetch are revisited early.
+    async fn enqueue_sitemap_urls(&self, frontier: Uuid, spec: &CrawlJobSpec, url: String, host: String) {
+        let host = host.as_str();
+        let mut sitemap_urls = self.robots_checker.sitemaps(&url, &self.config.user_agent).await;
+        if sitemap_urls.is_empty() {
+            match Url::parse(&url) {
+                Ok(parsed) => sitemap_urls.push(format!("{}/sitemap.xml", parsed.origin().ascii_serialization())),
+                Err(_) => return,
+            }
+        }
+
+        let entries = sitemap::discover(&self.client, &self.network, &self.politeness, sitemap_urls, &self.config.user_agent).await;
+        let mut urls = Vec::new();
+        let mut fetch_urls = Vec::new();
+        let mut priorities = Vec::new();
+        let mut lastmods = Vec::new();
+        let mut modified_urls = Vec::new();
+        let mut modified_at = Vec::new();
+        for entry in entries {
+            let Ok((normalized_url, fetch_url)) = self.frontier_url(&entry.loc) else {
+                continue;
+            };
+            // A sitemap may only list pages of its own host
+            if HostScheduler::host_key(&normalized_url) != host
+                || !spec.scope.allows(&normalized_url)
+                || !self.config.should_follow_link(&normalized_url, host) {
+                continue;
+            }
+            if let Some(lastmod) = entry.lastmod {
+                modified_urls.push(normalized_url.clone());
+                modified_at.push(lastmod);
+            }
+            priorities.push(PRIORITY_DEFAULT + entry.priority_hint());
+            lastmods.push(entry.lastmod);
+            fetch_urls.push(fetch_url);
+            urls.push(normalized_url);
+        }
+
+        if spec.max_depth >= SITEMAP_DEPTH as usize {
+            match self.storage.enqueue_urls(frontier, &urls, &fetch_urls, &priorities, &lastmods, SITEMAP_DEPTH).await {
+                Ok(added) => info!("Added {} of {} sitemap URLs of {} to the frontier", added, urls.len(), host),
+                Err(e) => warn!("Failed to enqueue sitemap URLs of {}: {}", host, e),
+            }
+        }
+
+        if frontier == SEED_FRONTIER {
+            match self.storage.expedite_revisits(&modified_urls, &modified_at).await {
+                Ok(0) => {}
+                Ok(expedited) => info!("{} stored pages of {} changed according to its sitemap", expedited, host),
+                Err(e) => warn!("Failed to expedite revisits for {}: {}", host, e),
+            }
+        }
+    }
+
+    /// Leases up to `slots` URLs to crawl from `frontier`. The seed crawl
+    /// splits them between due revisits and new frontier entries according to
+    /// `revisit_ratio`, and either side may use the slots the other one leaves
+    /// unused. Other jobs only crawl their own frontier.
+    async fn lease_work(&self, frontier: Uuid, slots: usize, max_depth: usize) -> Result<Vec<LeasedUrl>, Box<dyn Error + Send + Sync>> {
+        let revisits_enabled = frontier == SEED_FRONTIER;
+        let mut revisits = Vec::new();
+        if revisits_enabled {
+            let revisit_budget = (slots as f64 * self.config.revisit_ratio.clamp(0.0, 1.0)).round() as usize;
+            revisits = self.storage.lease_due_revisits(revisit_budget as i64, FRONTIER_LEASE_SECS).await?;
+        }
+
+        let entries = self.storage.lease_frontier_batch(
+            frontier,
+            self.lease_owner,
+            (slots - revisits.len()) as i64,
+            max_depth as i32,
+            FRONTIER_LEASE_SECS,
+        ).await?;
+
+        let unused = slots - revisits.len() - entries.len();
+        if revisits_enabled && unused > 0 {
+            revisits.extend(self.storage.lease_due_revisits(unused as i64, FRONTIER_LEASE_SECS).await?);
+        }
+
+        if !revisits.is_empty() {
+            debug!("Leased {} revisits and {} new URLs", revisits.len(), entries.len());
+        }
+
+        let mut leased: Vec<LeasedUrl> = entries.into_iter()
+            .map(|entry| (entry.url, entry.fetch_url, entry.depth as usize, LeaseSource::Frontier { id: entry.id, attempts: entry.attempts }))
+            .collect();
+        leased.extend(revisits.into_iter()
+            .map(|entry| (entry.url, entry.fetch_url, entry.depth as usize, LeaseSource::Revisit(entry.webpage_id))));
+        Ok(leased)
+    }
+
+    /// Parses and stores a fetched page. Returns `None` if the page was skipped
+    /// for its low quality or because it asked not to be indexed.
+    ///
+    /// Pages are stored under their canonical URL when they name one on the
+    /// same site. Pages that state no date of their own are dated by their
+    /// sitemap `lastmod`, or else their `Last-Modified` header.
+    async fn process_page(
+        &self,
+        frontier: Uuid,
+        spec: &CrawlJobSpec,
+        url: &str,
+        page: &FetchedPage,
+        depth: usize,
+        sitemap_lastmod: Option<DateTime<Utc>>,
+    ) -> Result<Option<SavedWebpage>, Box<dyn Error>> {
+        let status = page.status.as_u16() as i32;
+        let mut parsed_page = match page.mime_type.as_deref() {
+            Some("application/pdf") => parser::parse_pdf(&page.body, url, status)?,
+            Some("text/plain") => parser::parse_plain_text(&page.text(), url, status)?,
+            _ => parser::parse_webpage(&page.text(), url, status)?,
+        };
+        if parsed_page.last_updated_timestamp.is_none() {
+            let date = sitemap_lastmod.and_then(document_date::from_sitemap)
+                .or_else(|| page.last_modified.as_deref()
+                    .and_then(|value| document_date::from_last_modified(value, parsed_page.fetch_timestamp)));
+            if let Some(date) = date {
+                date.apply_to(&mut parsed_page);
+            }
+        }
+
+        let robots = parsed_page.robots
+            .merge(RobotsDirectives::from_header_values(&page.robots_tags, &self.config.user_agent));
+        if robots.nofollow {
+            parsed_page.links.clear();
+        }
+        if robots.noindex {
+            debug!("Not storing {}: noindex", url);
+            self.enqueue_links(frontier, spec, &parsed_page.links, &parsed_page.domain, depth).await;
+            return Ok(None);
+        }
+
+        let store_url = match parsed_page.canonical_url.as_deref().and_then(|canonical| canonical_target(&self.canonicalizer, url, canonical)) {
+            Some(canonical) => {
+                debug!("Storing {} under its canonical URL {}", url, canonical);
+                canonical
+            }
+            None => url.to_string(),
+        };
         
-        if quality_score < self.config.min_quality_score {
-            debug!("Skipping low quality page: {} (score: {})", url, quality_score);
-            return Ok(());
+        let fingerprint = parsed_page.content.as_deref().and_then(simhash::simhash).map(|hash| hash as i64);
+        let is_duplicate = match fingerprint {
+            Some(fingerprint) => self.storage
+                .has_near_duplicate(&store_url, fingerprint, self.config.max_duplicate_distance as i32)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to look up near-duplicates of {}: {}", url, e);
+                    false
+                }),
+            None => false,
+        };
+
+        let quality = self.quality_scorer.score(&PageSignals {
+            page: &parsed_page,
+            body_size: page.body.len(),
+            is_duplicate,
+        });
+        let quality_score = quality.total();
+        if quality_score < self.config.min_quality_score as f64 {
+            info!("Skipping low quality page: {} (score: {:.1} < {}; {})", url, quality_score, self.config.min_quality_score, quality);
+            return Ok(None);
         }
 
+        let content = parsed_page.content.take();
+        let description = parsed_page.meta_description.as_deref().filter(|_| self.config.prefer_meta_description);
+        let summary = summarizer::page_summary(content.as_deref().unwrap_or_default(), description, self.config.summary_max_chars);
         let webpage = Webpage {
             id: Uuid::new_v4(),
-            url: url.to_string(),
+            url: store_url,
             domain: parsed_page.domain.clone(),
             title: parsed_page.title,
-            content_summary: Some(summarizer::tfidf_summary(&parsed_page.content.unwrap_or_default(), 3)),
+            content_summary: Some(summary),
             fetch_timestamp: parsed_page.fetch_timestamp,
             last_updated_timestamp: parsed_page.last_updated_timestamp,
             status: parsed_page.status,
@@ -255,12 +952,46 @@ impl Crawler {
             ranked: false,
             last_ranked_at: None,
             page_rank: 0.0,
+            etag: page.etag.clone(),
+            last_modified: page.last_modified.clone(),
+            mime_type: page.mime_type.clone(),
+            simhash: fingerprint,
         };
 
-        info!("Saving webpage: {} (quality: {})", webpage.url, quality_score);
-        if let Err(e) = self.storage.save_webpage(&webpage).await {
-            warn!("Failed to save webpage {}: {}", webpage.url, e);
-            return Ok(());
+        // Pages stored before URLs were canonicalized move to their canonical key
+        if let Some(legacy_url) = canonicalizer::legacy_url_key(&webpage.url).filter(|legacy_url| *legacy_url != webpage.url) {
+            match self.storage.rename_webpage(&legacy_url, &webpage.url).await {
+                Ok(true) => info!("Moved {} to its canonical URL {}", legacy_url, webpage.url),
+                Ok(false) => {}
+                Err(e) => warn!("Failed to move {} to {}: {}", legacy_url, webpage.url, e),
+            }
+        }
+
+        info!("Saving webpage: {} (quality: {:.1})", webpage.url, quality_score);
+        // The summary only serves as a snippet; the index is built from the full text
+        let saved = match self.storage.save_webpage(&webpage, content.as_deref()).await {
+            Ok(saved) => saved,
+            Err(e) => {
+                warn!("Failed to save webpage {}: {}", webpage.url, e);
+                return Err(e.into());
+            }
+        };
+        let webpage_id = saved.id;
+        let content_changed = saved.content_changed;
+
+        if let Some(fingerprint) = fingerprint {
+            if let Err(e) = self.assign_duplicate_cluster(webpage_id, fingerprint).await {
+                warn!("Failed to check {} for near-duplicates: {}", webpage.url, e);
+            }
+        }
+
+        // Unchanged pages keep their stored links; changed ones get theirs replaced
+        if content_changed {
+            if let Err(e) = self.storage.delete_links_from(webpage_id).await {
+                warn!("Failed to delete old links of {}: {}", webpage.url, e);
+            }
+        } else {
+            debug!("Content of {} unchanged since last crawl", webpage.url);
         }
 
         let batch_size = 50;
@@ -284,36 +1015,30 @@ impl Crawler {
 
                 let mut had_error = false;
                 for link in links_chunk {
-                    if let Ok(normalized_url) = normalize_url(&link.target_url) {
+                    if let Ok(normalized_url) = self.canonicalizer.canonicalize(&link.target_url) {
                         if !self.config.should_follow_link(&normalized_url, &parsed_page.domain) {
                             continue;
                         }
 
-                        let db_link = Link {
-                            id: Uuid::new_v4(),
-                            source_webpage_id: webpage.id,
-                            target_url: normalized_url.clone(),
-                            anchor_text: link.anchor_text.clone(),
-                        };
-
-                        if let Err(e) = self.storage.save_link(&mut transaction, &db_link).await {
-                            warn!("Failed to save link {} -> {}: {}", webpage.url, link.target_url, e);
-                            had_error = true;
-                            break;
-                        }
-
-                        if depth < self.config.max_depth {
-                            let mut queue = self.queue.lock().await;
-                            let is_priority = self.config.priority_domains.as_ref()
-                                .map(|domains| domains.iter().any(|d| normalized_url.contains(d)))
-                                .unwrap_or(false);
+                        if content_changed {
+                            let db_link = Link {
+                                id: Uuid::new_v4(),
+                                source_webpage_id: webpage_id,
+                                target_url: normalized_url.clone(),
+                                anchor_text: link.anchor_text.clone(),
+                                rel: link.rel.clone(),
+                                kind: link.kind.as_str().to_string(),
+                                position: link.position.as_str().to_string(),
+                                is_internal: link.is_internal,
+                            };
 
-                            if is_priority {
-                                queue.push_front((normalized_url, depth + 1));
-                            } else {
-                                queue.push_back((normalized_url, depth + 1));
+                            if let Err(e) = self.storage.save_link(&mut transaction, &db_link).await {
+                                warn!("Failed to save link {} -> {}: {}", webpage.url, link.target_url, e);
+                                had_error = true;
+                                break;
                             }
                         }
+
                     }
                 }
 
@@ -338,32 +1063,110 @@ impl Crawler {
             }
         }
 
-        Ok(())
+        self.enqueue_links(frontier, spec, &parsed_page.links, &parsed_page.domain, depth).await;
+
+        Ok(Some(saved))
     }
 
-    fn calculate_crawl_quality_score(&self, page: &parser::ParsedWebpage) -> u32 {
-        let mut score = 0;
+    /// Puts a stored page into the cluster of its closest near-duplicate, or
+    /// makes it stand for itself when there is none. Each cluster has one
+    /// representative that the others point to; only it gets indexed. A page
+    /// that already represents others stays a representative, but members
+    /// its changed content no longer resembles leave its cluster.
+    async fn assign_duplicate_cluster(&self, webpage_id: Uuid, fingerprint: i64) -> Result<(), StorageError> {
+        let max_distance = self.config.max_duplicate_distance as i32;
+        let released = self.storage.release_diverged_duplicates(webpage_id, fingerprint, max_distance).await?;
+        if released > 0 {
+            debug!("{} pages are no longer near-duplicates of {}", released, webpage_id);
+        }
+
+        let candidates = self.storage.find_near_duplicates(
+            webpage_id,
+            fingerprint,
+            max_distance,
+            DUPLICATE_CANDIDATES,
+        ).await?;
 
-        if let Some(ref content) = page.content {
-            let word_count = content.split_whitespace().count();
-            score += (word_count as f32 / 1000.0 * 30.0).min(30.0) as u32;
+        let represents_others = candidates.iter().any(|candidate| candidate.duplicate_of == Some(webpage_id));
+        let representative = if represents_others {
+            None
+        } else {
+            candidates.first().map(|closest| closest.duplicate_of.unwrap_or(closest.id))
+        };
+        if let Some(representative) = representative {
+            debug!("Page {} is a near-duplicate of {}", webpage_id, representative);
         }
 
-        if page.title.is_some() { score += 5; }            
-        if page.meta_description.is_some() { score += 5; } 
-        if page.meta_keywords.is_some() { score += 5; }    
-        if page.metadata.is_some() { score += 5; }         
+        self.storage.set_duplicate_of(webpage_id, representative).await
+    }
+
+    /// Adds the page's links to the job's frontier, one level deeper. The job's
+    /// scope limits what is crawled, not which links are recorded. Links the
+    /// page does not vouch for (nofollow, ugc, sponsored) are not followed.
+    async fn enqueue_links(&self, frontier: Uuid, spec: &CrawlJobSpec, links: &[parser::ParsedLink], source_domain: &str, depth: usize) {
+        if depth >= spec.max_depth {
+            return;
+        }
 
-        let link_count = page.links.len();
-        score += ((link_count as f32 / 50.0 * 20.0).min(20.0)) as u32;
+        for link in links.iter().filter(|link| link.is_followable()) {
+            let (normalized_url, fetch_url) = match self.frontier_url(&link.target_url) {
+                Ok(urls) => urls,
+                Err(e) => {
+                    debug!("Not following {}: {}", link.target_url, e);
+                    continue;
+                }
+            };
+            if !self.config.should_follow_link(&normalized_url, source_domain) || !spec.scope.allows(&normalized_url) {
+                continue;
+            }
 
-        if let Some(ref priority_domains) = self.config.priority_domains {
-            if priority_domains.iter().any(|d| page.domain.contains(d)) {
-                score += 30;
+            let priority = self.frontier_priority(&normalized_url);
+            if let Err(e) = self.storage.enqueue_url(frontier, &normalized_url, fetch_url.as_deref(), (depth + 1) as i32, priority).await {
+                warn!("Failed to enqueue {}: {}", normalized_url, e);
             }
         }
+    }
 
-        score
+    /// Queues the target of a redirect to another host at the depth of the
+    /// redirecting URL, as the redirect is no link. Targets are filtered like
+    /// links; one that is not followed is recorded in the fetch log and
+    /// `false` returned.
+    async fn enqueue_redirect_target(&self, frontier: Uuid, spec: &CrawlJobSpec, target: &str, depth: usize) -> Result<bool, String> {
+        let (normalized_url, fetch_url) = self.frontier_url(target).map_err(|e| e.to_string())?;
+        if !self.config.should_follow_link(&normalized_url, "") || !spec.scope.allows(&normalized_url) {
+            self.log_fetch(FetchLogEntry {
+                url: normalized_url,
+                error_kind: Some("redirect_not_followed".to_string()),
+                ..Default::default()
+            }).await;
+            return Ok(false);
+        }
+        let priority = self.frontier_priority(&normalized_url);
+        self.storage.enqueue_url(frontier, &normalized_url, fetch_url.as_deref(), depth as i32, priority).await
+            .map(|_| true)
+            .map_err(|e| e.to_string())
+    }
+
+    /// The canonical form of a URL about to be queued, which identifies it,
+    /// and the URL to request for it when that differs.
+    fn frontier_url(&self, url: &str) -> Result<(String, Option<String>), CrawlerError> {
+        let normalized_url = self.canonicalizer.canonicalize_for_frontier(url)?;
+        let fetch_url = canonicalizer::fetch_url(url)?;
+        Ok((normalized_url.clone(), Some(fetch_url).filter(|fetch_url| *fetch_url != normalized_url)))
+    }
+
+    fn frontier_priority(&self, url: &str) -> i32 {
+        let is_priority = self.config.priority_domains.as_ref()
+            .map(|domains| domains.iter().any(|d| url.contains(d)))
+            .unwrap_or(false);
+        if is_priority { PRIORITY_DOMAIN } else { PRIORITY_DEFAULT }
+    }
+
+    /// Rejects a URL submitted for crawling that the network policy forbids,
+    /// such as one pointing into a private network.
+    pub async fn check_seed_url(&self, url: &str) -> Result<(), CrawlerError> {
+        let parsed = Url::parse(url.trim()).map_err(|e| CrawlerError::UrlNormalizationError(e.to_string()))?;
+        self.network.check_url_resolved(&parsed).await
     }
 
     /// Check if we have any existing results for a query
@@ -405,37 +1208,70 @@ impl Crawler {
         }
     }
 
-    pub async fn get_queue_size(&self) -> usize {
-        self.queue.lock().await.len()
+}
+
+/// The normalized canonical URL to store a page fetched from `url` under, if
+/// it differs. Canonicals pointing to another host are ignored (apart from a
+/// `www.` prefix), so that no page can overwrite another site's.
+fn canonical_target(canonicalizer: &UrlCanonicalizer, url: &str, canonical: &str) -> Option<String> {
+    let canonical = canonicalizer.canonicalize(canonical).ok()?;
+    let fetched = canonicalizer.canonicalize(url).ok()?;
+    if canonical == fetched {
+        return None;
     }
 
-    pub async fn get_visited_count(&self) -> usize {
-        self.visited.lock().await.len()
+    let host = |u: &str| Url::parse(u).ok()
+        .and_then(|u| u.host_str().map(|h| h.trim_start_matches("www.").to_lowercase()));
+    if host(&canonical).is_some() && host(&canonical) == host(&fetched) {
+        Some(canonical)
+    } else {
+        None
     }
 }
 
-pub fn normalize_url(url: &str) -> Result<String, CrawlerError> {
-    let parsed = Url::parse(url).map_err(|e| CrawlerError::UrlNormalizationError(e.to_string()))?;
-    let mut normalized = parsed.clone();
+/// How long to wait before fetching a URL again after `attempts` earlier
+/// temporary failures: exponential backoff, or the server's `Retry-After`
+/// when that is longer.
+fn retry_delay(retry_after: Option<Duration>, attempts: i32) -> Duration {
+    let backoff = Duration::from_secs_f64(RETRY_BASE_DELAY_SECS * 2f64.powi(attempts.clamp(0, 16)));
+    retry_after.map_or(backoff, |retry_after| retry_after.max(backoff)).min(MAX_RETRY_DELAY)
+}
 
-    if (parsed.scheme() == "http" && parsed.port() == Some(80)) ||
-       (parsed.scheme() == "https" && parsed.port() == Some(443)) {
-        normalized.set_port(None).ok();
+/// Runs `work` while making progress on the pending sitemap discoveries.
+async fn while_discovering<T>(discoveries: &mut FuturesUnordered<BoxFuture<'_, ()>>, work: impl Future<Output = T>) -> T {
+    tokio::pin!(work);
+    loop {
+        tokio::select! {
+            output = &mut work => return output,
+            Some(()) = discoveries.next(), if !discoveries.is_empty() => {}
+        }
     }
+}
 
-    let path = normalized.path().to_string();
-    let trimmed_path = path.trim_end_matches('/');
-    normalized.set_path(trimmed_path);
-
-    if let Some(query) = normalized.query() {
-        let mut params: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
-            .into_owned()
-            .collect();
-        params.sort_by(|a, b| a.0.cmp(&b.0));
-        normalized.set_query(Some(&url::form_urlencoded::Serializer::new(String::new())
-            .extend_pairs(params)
-            .finish()));
+/// How long until an entry of the frontier can be leased: zero if one can be
+/// right away, `None` if none is left. Waits are capped at `MAX_FRONTIER_WAIT`.
+fn time_until_leasable(backlog: &FrontierBacklog) -> Option<Duration> {
+    if backlog.ready > 0 {
+        return Some(Duration::ZERO);
     }
+    let next = backlog.next_not_before?;
+    let wait = (next - Utc::now()).to_std().unwrap_or_default();
+    Some(wait.clamp(Duration::from_secs(1), MAX_FRONTIER_WAIT))
+}
 
-    Ok(normalized.to_string().to_lowercase())
+/// The fetch log record of a fetch attempt.
+fn fetch_log_entry(fetched: &fetcher::FetchOutcome) -> FetchLogEntry {
+    let (status, error_kind, bytes) = match &fetched.result {
+        Ok(page) => (Some(page.status), None, Some(page.body.len() as i64)),
+        Err(e) => (e.status(), Some(e.kind().to_string()), None),
+    };
+    FetchLogEntry {
+        url: fetched.url.clone(),
+        final_url: Some(fetched.final_url().to_string()),
+        redirect_chain: fetched.redirects.urls.clone(),
+        status: status.map(|status| status.as_u16() as i32),
+        error_kind,
+        bytes,
+        latency_ms: Some(fetched.elapsed.as_millis().min(i32::MAX as u128) as i32),
+    }
 }
@@ -0,0 +1,187 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use lazy_static::lazy_static;
+use scraper::{Html, Selector};
+use serde_json::{json, Value};
+use crate::parser::ParsedWebpage;
+
+/// Dates before the web existed are taken for placeholders.
+const EARLIEST_PLAUSIBLE_YEAR: i32 = 1991;
+
+/// Servers that render pages on the fly tend to send the time of the request
+/// as `Last-Modified`; such values say nothing about the content.
+const DYNAMIC_LAST_MODIFIED_SECS: i64 = 60;
+
+lazy_static! {
+    static ref ARTICLE_TIME_SELECTOR: Selector = Selector::parse("article time[datetime], main time[datetime]").unwrap();
+    static ref TIME_SELECTOR: Selector = Selector::parse("time[datetime]").unwrap();
+}
+
+/// Where the date of a document was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateSource {
+    ArticleModified,
+    JsonLdModified,
+    MicrodataModified,
+    ArticlePublished,
+    JsonLdPublished,
+    MicrodataPublished,
+    TimeElement,
+    PdfInfo,
+    SitemapLastmod,
+    LastModifiedHeader,
+}
+
+impl DateSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateSource::ArticleModified => "article:modified_time",
+            DateSource::JsonLdModified => "json_ld:dateModified",
+            DateSource::MicrodataModified => "microdata:dateModified",
+            DateSource::ArticlePublished => "article:published_time",
+            DateSource::JsonLdPublished => "json_ld:datePublished",
+            DateSource::MicrodataPublished => "microdata:datePublished",
+            DateSource::TimeElement => "time",
+            DateSource::PdfInfo => "pdf_info",
+            DateSource::SitemapLastmod => "sitemap:lastmod",
+            DateSource::LastModifiedHeader => "last_modified",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DocumentDate {
+    pub timestamp: DateTime<Utc>,
+    pub source: DateSource,
+}
+
+impl DocumentDate {
+    fn new(timestamp: DateTime<Utc>, source: DateSource) -> Option<Self> {
+        is_plausible(timestamp).then_some(DocumentDate { timestamp, source })
+    }
+
+    /// Sets the page's `last_updated_timestamp` and records the source in its
+    /// metadata as `date_source`.
+    pub fn apply_to(&self, page: &mut ParsedWebpage) {
+        page.last_updated_timestamp = Some(self.timestamp);
+        let metadata = page.metadata.get_or_insert_with(|| json!({}));
+        metadata["date_source"] = json!(self.source.as_str());
+    }
+}
+
+/// The date a page states for itself, from its structured data (as extracted
+/// into `metadata`) or a `<time datetime>` element. Modification dates are
+/// preferred over publication dates.
+pub fn from_page(metadata: Option<&Value>, document: &Html) -> Option<DocumentDate> {
+    let article = |field: &str| metadata?.get("article")?.get(field).and_then(first_str).and_then(parse_date);
+    let opengraph = |field: &str| metadata?.get("opengraph")?.get(field).and_then(first_str).and_then(parse_date);
+    let json_ld = |field: &str| metadata?.get("json_ld")?.as_array()?
+        .iter()
+        .find_map(|item| item.get(field).and_then(first_str).and_then(parse_date));
+    let microdata = |field: &str| metadata?.get("microdata")?.as_array()?
+        .iter()
+        .find_map(|item| item.get("properties")?.get(field).and_then(first_str).and_then(parse_date));
+
+    let candidates = [
+        (article("modified_time").or_else(|| opengraph("updated_time")), DateSource::ArticleModified),
+        (json_ld("dateModified"), DateSource::JsonLdModified),
+        (microdata("dateModified"), DateSource::MicrodataModified),
+        (article("published_time"), DateSource::ArticlePublished),
+        (json_ld("datePublished"), DateSource::JsonLdPublished),
+        (microdata("datePublished"), DateSource::MicrodataPublished),
+        (time_element(document), DateSource::TimeElement),
+    ];
+    candidates.into_iter()
+        .find_map(|(timestamp, source)| timestamp.and_then(|timestamp| DocumentDate::new(timestamp, source)))
+}
+
+/// The date of a PDF from the `ModDate` or `CreationDate` of its info
+/// dictionary, in the PDF date format (`D:YYYYMMDDHHmmSSOHH'mm'`).
+pub fn from_pdf_info(value: &str) -> Option<DocumentDate> {
+    let value = value.trim().trim_start_matches("D:");
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 8 {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>, default: u32| {
+        digits.get(range).and_then(|s| s.parse().ok()).unwrap_or(default)
+    };
+    let local = NaiveDate::from_ymd_opt(field(0..4, 0) as i32, field(4..6, 1), field(6..8, 1))?
+        .and_hms_opt(field(8..10, 0), field(10..12, 0), field(12..14, 0))?;
+
+    let zone = &value[digits.len()..];
+    let offset_secs = match zone.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let numbers: Vec<i32> = zone[1..].split('\'').filter_map(|s| s.parse().ok()).collect();
+            let secs = numbers.first().copied().unwrap_or(0) * 3600 + numbers.get(1).copied().unwrap_or(0) * 60;
+            if sign == '-' { -secs } else { secs }
+        }
+        _ => 0,
+    };
+    let timestamp = FixedOffset::east_opt(offset_secs)?.from_local_datetime(&local).single()?;
+    DocumentDate::new(timestamp.with_timezone(&Utc), DateSource::PdfInfo)
+}
+
+pub fn from_sitemap(lastmod: DateTime<Utc>) -> Option<DocumentDate> {
+    DocumentDate::new(lastmod, DateSource::SitemapLastmod)
+}
+
+/// The date from a `Last-Modified` header, unless it is just the time the
+/// response was generated.
+pub fn from_last_modified(value: &str, fetched_at: DateTime<Utc>) -> Option<DocumentDate> {
+    let timestamp = DateTime::parse_from_rfc2822(value.trim()).ok()?.with_timezone(&Utc);
+    if (fetched_at - timestamp).num_seconds().abs() <= DYNAMIC_LAST_MODIFIED_SECS {
+        return None;
+    }
+    DocumentDate::new(timestamp, DateSource::LastModifiedHeader)
+}
+
+fn time_element(document: &Html) -> Option<DateTime<Utc>> {
+    document.select(&ARTICLE_TIME_SELECTOR)
+        .chain(document.select(&TIME_SELECTOR))
+        .filter_map(|time| time.value().attr("datetime"))
+        .find_map(parse_date)
+}
+
+fn first_str(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(s) => Some(s),
+        Value::Array(values) => values.iter().find_map(first_str),
+        _ => None,
+    }
+}
+
+/// Parses the date formats found in page metadata: RFC 3339 and its common
+/// variants (no seconds, no zone, space separator), RFC 2822, or a bare date.
+/// Times without a zone are taken as UTC.
+pub fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M%:z", "%Y-%m-%dT%H:%M:%S%z", "%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%:z"] {
+        if let Ok(dt) = DateTime::parse_from_str(value, format) {
+            return Some(dt.with_timezone(&Utc));
+        }
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc2822(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(dt.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+}
+
+/// Rejects placeholder dates from before the web and dates in the future
+/// (allowing a day for clock and time zone mix-ups).
+fn is_plausible(timestamp: DateTime<Utc>) -> bool {
+    let earliest = NaiveDate::from_ymd_opt(EARLIEST_PLAUSIBLE_YEAR, 1, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc());
+    earliest.is_some_and(|earliest| timestamp >= earliest) && timestamp <= Utc::now() + chrono::Duration::days(1)
+}
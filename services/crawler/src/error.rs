@@ -1,3 +1,4 @@
+use std::time::Duration;
 use thiserror::Error;
 use reqwest::StatusCode;
 
@@ -9,6 +10,11 @@ pub enum CrawlerError {
     #[error("HTTP request failed with status: {0}")]
     StatusError(StatusCode),
 
+    /// A status asking to come back later (429, 503 and other temporary
+    /// server failures), with the server's `Retry-After` if it sent one
+    #[error("HTTP request failed with retryable status: {0}")]
+    RetryableStatus(StatusCode, Option<Duration>),
+
     #[error("Failed to normalize URL: {0}")]
     UrlNormalizationError(String),
 
@@ -29,4 +35,82 @@ pub enum CrawlerError {
 
     #[error("Content too large: {0} bytes")]
     ContentTooLarge(u64),
+
+    #[error("Invalid crawl scope: {0}")]
+    InvalidScope(String),
+
+    #[error("Invalid sitemap: {0}")]
+    SitemapError(String),
+
+    #[error("Unsupported content type: {0}")]
+    UnsupportedContentType(String),
+
+    #[error("Crawl trap: {0}")]
+    CrawlTrap(String),
+
+    #[error("More than {0} redirects")]
+    TooManyRedirects(usize),
+
+    /// A redirect to another host, whose robots.txt and request interval
+    /// apply to the target; it is queued rather than followed
+    #[error("Redirected to another host: {0}")]
+    CrossHostRedirect(String),
+
+    #[error("Blocked by network policy: {0}")]
+    Blocked(String),
+}
+
+impl CrawlerError {
+    /// Whether the failure is likely temporary, so that the URL is worth
+    /// fetching again later. Permanent failures (404, 410, oversized or
+    /// unsupported content, ...) are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            CrawlerError::RetryableStatus(..) | CrawlerError::MaxRetriesReached | CrawlerError::ResponseError(_) => true,
+            CrawlerError::StatusError(status) => is_retryable_status(*status),
+            CrawlerError::RequestError(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// Short name of the kind of failure, as recorded in the fetch log.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CrawlerError::RequestError(e) if e.is_timeout() => "timeout",
+            CrawlerError::RequestError(e) if e.is_connect() => "connect",
+            CrawlerError::RequestError(_) => "request",
+            CrawlerError::StatusError(_) | CrawlerError::RetryableStatus(..) => "http_status",
+            CrawlerError::MaxRetriesReached => "transport",
+            CrawlerError::ResponseError(_) => "body",
+            CrawlerError::ContentTooLarge(_) => "too_large",
+            CrawlerError::UnsupportedContentType(_) => "unsupported_content_type",
+            CrawlerError::TooManyRedirects(_) => "too_many_redirects",
+            CrawlerError::CrossHostRedirect(_) => "cross_host_redirect",
+            CrawlerError::Blocked(_) => "blocked",
+            CrawlerError::UrlNormalizationError(_) | CrawlerError::CrawlTrap(_) => "invalid_url",
+            _ => "other",
+        }
+    }
+
+    /// The HTTP status the failure was reported with, if any.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            CrawlerError::StatusError(status) | CrawlerError::RetryableStatus(status, _) => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// How long the server asked us to wait before the next request.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            CrawlerError::RetryableStatus(_, retry_after) => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Statuses reporting a temporary condition: timeouts, rate limiting and
+/// server errors other than `501 Not Implemented`.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 425 | 429 | 500 | 502 | 503 | 504 | 520..=524)
 }
\ No newline at end of file
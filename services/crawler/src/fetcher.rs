@@ -1,85 +1,349 @@
-use reqwest::{Client, ClientBuilder, StatusCode};
+use reqwest::{Client, ClientBuilder, Response, StatusCode};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, Semaphore};
 use tokio::task;
 use tokio::time::sleep;
+use chrono::{DateTime, Utc};
 use log::{error, warn, info};
-use reqwest::header::{ACCEPT_ENCODING, USER_AGENT};
+use reqwest::header::{HeaderName, ACCEPT_ENCODING, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LOCATION, RETRY_AFTER, USER_AGENT};
 use rand::Rng;
-use crate::error::CrawlerError;
+use storage::CacheValidators;
+use url::Url;
+use crate::content_type::{self, ContentType};
+use crate::error::{self, CrawlerError};
+use crate::network::{self, NetworkPolicy};
 
 const MAX_RETRIES: usize = 3;  // Number of retry attempts allowed
 
-// Function to create an HTTP client with predefined settings
-pub fn create_http_client() -> Result<Client, reqwest::Error> {
+/// Most redirects followed for one page.
+const MAX_REDIRECTS: usize = 10;
+
+/// A fetched response. For `304 Not Modified` the body is empty.
+#[derive(Debug)]
+pub struct FetchedPage {
+    /// Response body, after transfer decompression
+    pub body: Vec<u8>,
+    pub status: StatusCode,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// MIME type from the `Content-Type` header, or sniffed from the body
+    /// when the header is missing
+    pub mime_type: Option<String>,
+    /// `charset` parameter of the `Content-Type` header
+    pub charset: Option<String>,
+    /// Values of all `X-Robots-Tag` headers
+    pub robots_tags: Vec<String>,
+}
+
+/// Redirects followed while fetching a page.
+#[derive(Debug, Clone, Default)]
+pub struct RedirectChain {
+    /// Every URL from the requested one to the final one; empty when the
+    /// request was not redirected
+    pub urls: Vec<String>,
+    /// Status of the first redirect
+    pub status: Option<StatusCode>,
+}
+
+/// One URL of a batch to fetch.
+#[derive(Debug, Clone)]
+pub struct FetchRequest {
+    /// The URL the outcome is reported under
+    pub url: String,
+    /// The URL to request, when it differs from `url`
+    pub fetch_url: Option<String>,
+    pub depth: usize,
+    /// Validators of the stored page, for a conditional request
+    pub validators: Option<CacheValidators>,
+}
+
+/// The result of fetching one URL of a batch.
+#[derive(Debug)]
+pub struct FetchOutcome {
+    pub url: String,
+    pub depth: usize,
+    /// Time from sending the request to the end of the body, or the failure
+    pub elapsed: Duration,
+    pub redirects: RedirectChain,
+    pub result: Result<FetchedPage, CrawlerError>,
+}
+
+impl FetchOutcome {
+    /// The URL the response came from, after redirects.
+    pub fn final_url(&self) -> &str {
+        self.redirects.urls.last().unwrap_or(&self.url)
+    }
+}
+
+impl FetchedPage {
+    pub fn is_not_modified(&self) -> bool {
+        self.status == StatusCode::NOT_MODIFIED
+    }
+
+    /// The body decoded as text, see [`content_type::detect_encoding`].
+    pub fn text(&self) -> String {
+        content_type::decode(&self.body, self.mime_type.as_deref(), self.charset.as_deref())
+    }
+}
+
+fn client_builder(network: &Arc<NetworkPolicy>) -> ClientBuilder {
     ClientBuilder::new()
+        .dns_resolver(network.resolver())
         .timeout(Duration::from_secs(30))  // Increased timeout
         .connect_timeout(Duration::from_secs(10))  // Added connect timeout
-        .redirect(reqwest::redirect::Policy::limited(10))  // Handle up to 10 redirects
         .gzip(true) //makes the client will decompress gzip-compressed responses it receives from the server.
         .pool_max_idle_per_host(10) // allows the HTTP client to maintain up to 10 idle connections per host.
         // This means that the client can reuse these connections for new requests to the same host
+}
+
+// Function to create an HTTP client with predefined settings, following
+// redirects on its own (robots.txt, sitemaps). Connections and redirects
+// are restricted by `network`; the first URL is the caller's to check.
+pub fn create_http_client(network: &Arc<NetworkPolicy>) -> Result<Client, reqwest::Error> {
+    client_builder(network)
+        .redirect(network.redirect_policy(MAX_REDIRECTS))
         .build()
 }
 
+// Client for fetching pages. It does not follow redirects: `fetch_page` does,
+// recording and checking every hop.
+pub fn create_page_client(network: &Arc<NetworkPolicy>) -> Result<Client, reqwest::Error> {
+    client_builder(network)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+}
+
+
+
+fn header_value(response: &Response, name: HeaderName) -> Option<String> {
+    response.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Parses a `Retry-After` header: either delay-seconds or an HTTP-date.
+/// A date in the past means no wait.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// The absolute http(s) URL a redirect response points to.
+fn redirect_target(url: &str, response: &Response) -> Option<String> {
+    let location = header_value(response, LOCATION)?;
+    let target = Url::parse(url).ok()?.join(location.trim()).ok()?;
+    matches!(target.scheme(), "http" | "https").then(|| target.to_string())
+}
 
+/// Whether `target` is served by the same scheme, host and port as `url`,
+/// so that the same robots.txt and request interval apply to it.
+fn same_origin(url: &Url, target: &str) -> bool {
+    Url::parse(target).is_ok_and(|target| target.origin() == url.origin())
+}
 
-// Function to fetch a page with retries and exponential backoff
-pub async fn fetch_page(url: &str, client: &Client, user_agent: &str, max_content_size: usize) -> Result<(String, StatusCode), CrawlerError> {
+fn header_values(response: &Response, name: &str) -> Vec<String> {
+    response.headers()
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Reads the body chunk by chunk and gives up as soon as it grows past
+/// `max_content_size`, so a missing or wrong `Content-Length` cannot make us
+/// download an arbitrarily large file. With `sniff`, the body's type is
+/// guessed from its first bytes and unsupported content is abandoned early.
+async fn read_body(
+    url: &str,
+    mut response: Response,
+    max_content_size: usize,
+    sniff: bool,
+) -> Result<(Vec<u8>, Option<&'static str>), CrawlerError> {
+    let mut body = Vec::new();
+    let mut sniffed = None;
+
+    loop {
+        let chunk = response.chunk().await.map_err(CrawlerError::ResponseError)?;
+        let done = chunk.is_none();
+        if let Some(chunk) = chunk {
+            body.extend_from_slice(&chunk);
+            if body.len() > max_content_size {
+                warn!("Content too large for {}: more than {} bytes", url, max_content_size);
+                return Err(CrawlerError::ContentTooLarge(body.len() as u64));
+            }
+        }
+
+        if sniff && sniffed.is_none() && (done || body.len() >= content_type::SNIFF_LEN) {
+            let mime_type = content_type::sniff(&body);
+            if !content_type::is_supported(mime_type) {
+                return Err(CrawlerError::UnsupportedContentType(mime_type.to_string()));
+            }
+            sniffed = Some(mime_type);
+        }
+
+        if done {
+            return Ok((body, sniffed));
+        }
+    }
+}
+
+// Function to fetch a page with retries and exponential backoff. When the page
+// was fetched before, its validators turn the request into a conditional GET.
+// Redirects are followed here rather than by the client, and recorded in
+// `redirects`, so that the caller learns where the page ended up. Every URL
+// requested is checked against `network` first. A redirect to another origin
+// ends the fetch with `CrossHostRedirect`: the caller queues the target, so
+// that its host's robots.txt and politeness are respected.
+pub async fn fetch_page(
+    url: &str,
+    client: &Client,
+    network: &NetworkPolicy,
+    user_agent: &str,
+    max_content_size: usize,
+    validators: Option<&CacheValidators>,
+    redirects: &mut RedirectChain,
+) -> Result<FetchedPage, CrawlerError> {
+    let mut current = url.to_string();
     let mut retries = 0;
     let mut delay = 1000; // Start with a 1-second delay
 
     loop {
-        info!("Attempting to fetch URL: {} (attempt {})", url, retries + 1);
-        match client.get(url)
+        let parsed = Url::parse(&current).map_err(|e| CrawlerError::UrlNormalizationError(e.to_string()))?;
+        if let Err(e) = network.check_url(&parsed) {
+            warn!("Not fetching {}: {}", current, e);
+            return Err(e);
+        }
+
+        info!("Attempting to fetch URL: {} (attempt {})", current, retries + 1);
+        let mut request = client.get(&current)
             .header(USER_AGENT, user_agent)
-            .header(ACCEPT_ENCODING, "gzip, deflate, br")
-            .send()
-            .await
-        {
+            .header(ACCEPT_ENCODING, "gzip, deflate, br");
+        // The validators belong to the requested URL only
+        if let Some(validators) = validators.filter(|_| redirects.urls.is_empty()) {
+            if let Some(ref etag) = validators.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(ref last_modified) = validators.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        match request.send().await {
             Ok(response) => {
                 let status = response.status();
-                info!("Received response for {}: {}", url, status);
-                
+                info!("Received response for {}: {}", current, status);
+
+                let etag = header_value(&response, ETAG);
+                let last_modified = header_value(&response, LAST_MODIFIED);
+
+                if status == StatusCode::NOT_MODIFIED {
+                    info!("{} not modified since last fetch", current);
+                    return Ok(FetchedPage {
+                        body: Vec::new(),
+                        status,
+                        etag,
+                        last_modified,
+                        mime_type: None,
+                        charset: None,
+                        robots_tags: Vec::new(),
+                    });
+                }
+
+                if status.is_redirection() {
+                    let target = redirect_target(&current, &response)
+                        .ok_or(CrawlerError::StatusError(status))?;
+                    if redirects.urls.is_empty() {
+                        redirects.urls.push(current.clone());
+                        redirects.status = Some(status);
+                    }
+                    if redirects.urls.len() > MAX_REDIRECTS {
+                        warn!("Too many redirects for URL {}", url);
+                        return Err(CrawlerError::TooManyRedirects(MAX_REDIRECTS));
+                    }
+                    info!("{} redirects ({}) to {}", current, status, target);
+                    redirects.urls.push(target.clone());
+                    if !same_origin(&parsed, &target) {
+                        return Err(CrawlerError::CrossHostRedirect(target));
+                    }
+                    current = target;
+                    retries = 0;
+                    delay = 1000;
+                    continue;
+                }
+
+                if error::is_retryable_status(status) {
+                    let retry_after = header_value(&response, RETRY_AFTER).and_then(|value| parse_retry_after(&value));
+                    warn!("Received retryable status code {} for URL {} (retry after {:?})", status, current, retry_after);
+                    return Err(CrawlerError::RetryableStatus(status, retry_after));
+                }
+
                 if !status.is_success() {
-                    warn!("Received non-success status code {} for URL {}", status, url);
+                    warn!("Received non-success status code {} for URL {}", status, current);
                     return Err(CrawlerError::StatusError(status));
                 }
                 
+                // Unsupported content is rejected before its body is downloaded
+                let content_type = header_value(&response, CONTENT_TYPE).and_then(|value| ContentType::parse(&value));
+                if let Some(ref content_type) = content_type {
+                    if !content_type::is_supported(&content_type.mime_type) {
+                        info!("Skipping {}: unsupported content type {}", current, content_type.mime_type);
+                        return Err(CrawlerError::UnsupportedContentType(content_type.mime_type.clone()));
+                    }
+                }
+
+                let robots_tags = header_values(&response, "x-robots-tag");
                 let content_length = response.content_length().unwrap_or(0);
-                info!("Content length for {}: {} bytes", url, content_length);
+                info!("Content length for {}: {} bytes", current, content_length);
                 
                 if content_length > max_content_size as u64 {
-                    warn!("Content too large for {}: {} bytes", url, content_length);
+                    warn!("Content too large for {}: {} bytes", current, content_length);
                     return Err(CrawlerError::ContentTooLarge(content_length));
                 }
                 
-                match response.text().await {
-                    Ok(content) => {
-                        info!("Successfully extracted text content from {}: {} bytes", url, content.len());
-                        if content.len() > max_content_size {
-                            warn!("Content too large after extraction for {}: {} bytes", url, content.len());
-                            return Err(CrawlerError::ContentTooLarge(content.len() as u64));
-                        }
-                        return Ok((content, status));
+                match read_body(&current, response, max_content_size, content_type.is_none()).await {
+                    Ok((body, sniffed)) => {
+                        info!("Successfully read content from {}: {} bytes", current, body.len());
+                        let (mime_type, charset) = match content_type {
+                            Some(content_type) => (content_type.mime_type, content_type.charset),
+                            None => (sniffed.unwrap_or_default().to_string(), None),
+                        };
+                        return Ok(FetchedPage {
+                            body,
+                            status,
+                            etag,
+                            last_modified,
+                            mime_type: Some(mime_type),
+                            charset,
+                            robots_tags,
+                        });
                     }
                     Err(e) => {
-                        error!("Failed to get response text for URL {}: {}", url, e);
-                        return Err(CrawlerError::ResponseError(e));
+                        error!("Failed to read response body for URL {}: {}", current, e);
+                        return Err(e);
                     }
                 }
             }
             Err(e) => {
-                error!("Failed to send request for URL {} (attempt {}): {}", url, retries + 1, e);
+                if let Some(reason) = network::blocked_reason(&e) {
+                    warn!("Not fetching {}: {}", current, reason);
+                    return Err(CrawlerError::Blocked(reason));
+                }
+                error!("Failed to send request for URL {} (attempt {}): {}", current, retries + 1, e);
                 if retries >= MAX_RETRIES {
-                    error!("Max retries reached for URL {}", url);
+                    error!("Max retries reached for URL {}", current);
                     return Err(CrawlerError::MaxRetriesReached);
                 }
                 retries += 1;
-                let jitter = rand::thread_rng().gen_range(0..=200);
+                let jitter = rand::rng().random_range(0..=200);
                 let total_delay = delay + jitter;
-                info!("Retrying URL {} in {} ms", url, total_delay);
+                info!("Retrying URL {} in {} ms", current, total_delay);
                 sleep(Duration::from_millis(total_delay)).await;
                 delay *= 2; // Exponential backoff
             }
@@ -87,30 +351,35 @@ pub async fn fetch_page(url: &str, client: &Client, user_agent: &str, max_conten
     }
 }
 
-// Function to fetch multiple pages in parallel
+// Function to fetch multiple pages in parallel. Per-host spacing between
+// requests is the caller's job (see `scheduler::HostScheduler`).
 pub async fn fetch_pages_in_parallel(
-    urls: Vec<(String, usize)>,
+    requests: Vec<FetchRequest>,
     client: &Client,
-    delay_ms: u64,
+    network: &Arc<NetworkPolicy>,
     max_concurrent_requests: usize,
     user_agent: &str,
     max_content_size: usize,
-) -> Vec<(String, usize, Result<(String, StatusCode), CrawlerError>)> {
+) -> Vec<FetchOutcome> {
     let semaphore = Arc::new(Semaphore::new(max_concurrent_requests));
     let results = Arc::new(Mutex::new(Vec::new()));
     let mut tasks = vec![];
 
-    for (url, depth) in urls {
+    for FetchRequest { url, fetch_url, depth, validators } in requests {
         let client = client.clone();
+        let network = Arc::clone(network);
         let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap();
         let results = Arc::clone(&results);
         let user_agent = user_agent.to_string();
+        let fetch_url = fetch_url.unwrap_or_else(|| url.clone());
 
         let task = task::spawn(async move {
             let _permit = permit;
-            let result = fetch_page(&url, &client, &user_agent, max_content_size).await;
-            results.lock().await.push((url.clone(), depth, result));
-            sleep(Duration::from_millis(delay_ms)).await;
+            let started = Instant::now();
+            let mut redirects = RedirectChain::default();
+            let result = fetch_page(&fetch_url, &client, &network, &user_agent, max_content_size, validators.as_ref(), &mut redirects).await;
+            let elapsed = started.elapsed();
+            results.lock().await.push(FetchOutcome { url, depth, elapsed, redirects, result });
         });
         tasks.push(task);
     }
@@ -1,8 +1,20 @@
+pub mod canonicalizer;
 pub mod config;
+pub mod content_extractor;
+pub mod content_type;
 pub mod crawler;
+pub mod document_date;
 pub mod error;
 pub mod fetcher;
 pub mod metrics;
+pub mod network;
 pub mod parser;
+pub mod quality;
+pub mod revisit;
 pub mod robots;
+pub mod scheduler;
+pub mod scope;
+pub mod simhash;
+pub mod sitemap;
+pub mod structured_data;
 pub mod summarizer;
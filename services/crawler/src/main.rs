@@ -8,9 +8,10 @@ use crate::error::CrawlerError;
 use crate::config::Config;
 use crate::crawler::Crawler;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use crate::fetcher::create_http_client;
+use crate::fetcher::{create_http_client, create_page_client};
+use crate::network::NetworkPolicy;
 use storage::PostgresStorage;
+use uuid::Uuid;
 use dotenv;
 
 async fn metrics() -> HttpResponse {
@@ -65,26 +66,44 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let current_dir = std::env::current_dir()?;
     let config_path = find_config_file(&current_dir);
     let config = Config::from_file(config_path)?;
-    let client = create_http_client()?;
+    let network = Arc::new(NetworkPolicy::from_config(&config));
+    let client = create_http_client(&network)?;
+    let page_client = create_page_client(&network)?;
     let database_url = std::env::var("DATABASE_URL")
         .map_err(|_| CrawlerError::EnvError(std::env::VarError::NotPresent))?;
     let storage = PostgresStorage::new(&database_url).await?;
+    storage.migrate().await?;
+
+    let storage_data = web::Data::new(storage.clone());
+    let purged = storage.purge_finished_job_frontiers().await?;
+    if purged > 0 {
+        info!("Removed {} frontier entries of finished jobs", purged);
+    }
+
+    // The initial crawl is recorded as a job like any other
+    let initial_job_id = Uuid::new_v4();
+    let initial_params = serde_json::json!({
+        "seed_urls": config.seed_urls,
+        "max_depth": config.max_depth,
+        "max_pages": config.max_pages,
+    });
     
-    // Create crawler instance and wrap it in Arc<Mutex>
-    let crawler = Arc::new(Mutex::new(Crawler::new(client, config.clone(), storage)));
+    // The crawler synchronizes its own state, so jobs share it through an Arc
+    let crawler = Arc::new(Crawler::new(client, page_client, network, config.clone(), storage.clone()));
     let crawler_data = web::Data::new(crawler.clone());
+    storage.create_crawl_job(initial_job_id, crawler.instance_id(), &initial_params).await?;
+
+    // Jobs of a previous run cannot be resumed, their crawl state is gone; they
+    // are failed once they stop reporting in, while jobs of other running
+    // crawlers carry on
+    let watcher = crawler.clone();
+    tokio::spawn(async move { watcher.watch_jobs().await });
     
     // Start initial crawl in background
     let crawler_clone = crawler.clone();
     tokio::spawn(async move {
         info!("Starting initial crawl with seed URLs...");
-        let crawler_guard = crawler_clone.lock().await;
-        if let Err(e) = crawler_guard.initialize().await {
-            error!("Failed to initialize crawler: {}", e);
-            return;
-        }
-        
-        if let Err(e) = crawler_guard.crawl_with_params(config.max_depth, config.max_pages).await {
+        if let Err(e) = crawler_clone.run_seed_crawl(initial_job_id).await {
             error!("Initial crawl failed: {}", e);
         }
         info!("Initial crawl completed");
@@ -94,7 +113,11 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let api_server = HttpServer::new(move || {
         App::new()
             .app_data(crawler_data.clone())
+            .app_data(storage_data.clone())
             .service(api::crawl)
+            .service(api::list_jobs)
+            .service(api::get_job)
+            .service(api::cancel_job)
             .service(api::get_job_status)
     })
     .bind("0.0.0.0:8000")?
@@ -119,10 +142,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
 }
 
 mod config;
+mod canonicalizer;
+mod content_extractor;
+mod content_type;
 mod crawler;
+mod document_date;
 mod fetcher;
 mod parser;
+mod quality;
+mod revisit;
 mod robots;
+mod scheduler;
+mod scope;
+mod simhash;
+mod sitemap;
+mod structured_data;
 mod summarizer;
 mod metrics;
+mod network;
 mod error;
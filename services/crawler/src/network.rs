@@ -0,0 +1,245 @@
+use std::error::Error;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use ipnet::IpNet;
+use lazy_static::lazy_static;
+use log::warn;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect;
+use url::{Host, Url};
+use crate::config::Config;
+use crate::error::CrawlerError;
+
+lazy_static! {
+    /// Special-purpose networks (RFC 6890) that are never crawled: private,
+    /// loopback, link-local, multicast, shared (CGNAT), documentation and
+    /// reserved ranges.
+    static ref BLOCKED_NETWORKS: Vec<IpNet> = [
+        "0.0.0.0/8", "10.0.0.0/8", "100.64.0.0/10", "127.0.0.0/8", "169.254.0.0/16",
+        "172.16.0.0/12", "192.0.0.0/24", "192.0.2.0/24", "192.168.0.0/16", "198.18.0.0/15",
+        "198.51.100.0/24", "203.0.113.0/24", "224.0.0.0/4", "240.0.0.0/4",
+        "::/96", "100::/64", "2001:db8::/32", "fc00::/7", "fe80::/10", "fec0::/10", "ff00::/8",
+    ]
+    .iter()
+    .map(|network| network.parse().expect("valid network"))
+    .collect();
+}
+
+/// Decides which hosts the crawler may connect to, so that URLs submitted
+/// through the API (or reached through redirects) cannot make it request
+/// internal services.
+///
+/// Only http(s) URLs on the allowed ports are fetched, and host names must
+/// resolve to public addresses. The checks apply at connect time, through
+/// [`NetworkPolicy::resolver`], so a DNS answer cannot change between check
+/// and request. Hosts and networks on the allowlist skip the port and address
+/// checks.
+#[derive(Debug, Clone)]
+pub struct NetworkPolicy {
+    allowed_ports: Vec<u16>,
+    allowed_hosts: Vec<HostRule>,
+    allowed_networks: Vec<IpNet>,
+}
+
+#[derive(Debug, Clone)]
+enum HostRule {
+    Exact(String),
+    /// `*.example.test`, stored as `.example.test`
+    Subdomains(String),
+}
+
+impl HostRule {
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            HostRule::Exact(exact) => host.eq_ignore_ascii_case(exact),
+            HostRule::Subdomains(suffix) => host.to_ascii_lowercase().ends_with(suffix.as_str()),
+        }
+    }
+}
+
+impl NetworkPolicy {
+    /// A policy allowing `allowed_ports`, plus any port and address of the
+    /// `allowlist` entries: host names (`*.` for all subdomains), addresses
+    /// and networks in CIDR notation.
+    pub fn new<S: AsRef<str>>(allowed_ports: &[u16], allowlist: &[S]) -> Self {
+        let mut allowed_hosts = Vec::new();
+        let mut allowed_networks = Vec::new();
+        for entry in allowlist.iter().map(|entry| entry.as_ref().trim().to_ascii_lowercase()).filter(|entry| !entry.is_empty()) {
+            if let Ok(network) = entry.parse::<IpNet>() {
+                allowed_networks.push(network);
+            } else if let Ok(address) = entry.parse::<IpAddr>() {
+                allowed_networks.push(IpNet::from(address));
+            } else if let Some(domain) = entry.strip_prefix("*.") {
+                allowed_hosts.push(HostRule::Subdomains(format!(".{}", domain)));
+            } else {
+                allowed_hosts.push(HostRule::Exact(entry));
+            }
+        }
+        NetworkPolicy {
+            allowed_ports: allowed_ports.to_vec(),
+            allowed_hosts,
+            allowed_networks,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(&config.allowed_ports, &config.network_allowlist)
+    }
+
+    /// Checks scheme, port and literal IP addresses of a URL. Host names are
+    /// checked when they are resolved.
+    pub fn check_url(&self, url: &Url) -> Result<(), CrawlerError> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(CrawlerError::Blocked(format!("scheme '{}' is not allowed", url.scheme())));
+        }
+        let Some(host) = url.host() else {
+            return Err(CrawlerError::Blocked(format!("{} has no host", url)));
+        };
+        if self.is_allowlisted(&host) {
+            return Ok(());
+        }
+
+        let port = url.port_or_known_default().unwrap_or_default();
+        if !self.allowed_ports.contains(&port) {
+            return Err(CrawlerError::Blocked(format!("port {} is not allowed", port)));
+        }
+        match host {
+            Host::Ipv4(address) => self.check_address(IpAddr::V4(address)),
+            Host::Ipv6(address) => self.check_address(IpAddr::V6(address)),
+            Host::Domain(domain) if domain == "localhost" || domain.ends_with(".localhost") => {
+                Err(CrawlerError::Blocked(format!("{} is a loopback host", domain)))
+            }
+            Host::Domain(_) => Ok(()),
+        }
+    }
+
+    /// Like [`check_url`](Self::check_url), and also resolves the host name
+    /// and requires at least one allowed address. Meant for validating input
+    /// up front; names that do not resolve are left to fail when fetched.
+    pub async fn check_url_resolved(&self, url: &Url) -> Result<(), CrawlerError> {
+        self.check_url(url)?;
+        let Some(Host::Domain(domain)) = url.host() else {
+            return Ok(());
+        };
+        if self.is_allowed_host(domain) {
+            return Ok(());
+        }
+        let Ok(addresses) = tokio::net::lookup_host((domain, 0)).await else {
+            return Ok(());
+        };
+        let addresses: Vec<IpAddr> = addresses.map(|address| address.ip()).collect();
+        if !addresses.is_empty() && !addresses.iter().any(|address| self.is_allowed_address(*address)) {
+            return Err(CrawlerError::Blocked(format!("{} resolves to non-public addresses only", domain)));
+        }
+        Ok(())
+    }
+
+    fn check_address(&self, address: IpAddr) -> Result<(), CrawlerError> {
+        if self.is_allowed_address(address) {
+            Ok(())
+        } else {
+            Err(CrawlerError::Blocked(format!("{} is not a public address", address)))
+        }
+    }
+
+    pub fn is_allowed_address(&self, address: IpAddr) -> bool {
+        is_public_address(address) || self.allowed_networks.iter().any(|network| network.contains(&address))
+    }
+
+    fn is_allowed_host(&self, host: &str) -> bool {
+        self.allowed_hosts.iter().any(|rule| rule.matches(host))
+    }
+
+    fn is_allowlisted(&self, host: &Host<&str>) -> bool {
+        match host {
+            Host::Domain(domain) => self.is_allowed_host(domain),
+            Host::Ipv4(address) => self.allowed_networks.iter().any(|network| network.contains(&IpAddr::V4(*address))),
+            Host::Ipv6(address) => self.allowed_networks.iter().any(|network| network.contains(&IpAddr::V6(*address))),
+        }
+    }
+
+    /// A DNS resolver dropping the addresses this policy does not allow.
+    pub fn resolver(self: &Arc<Self>) -> Arc<PolicyResolver> {
+        Arc::new(PolicyResolver { policy: Arc::clone(self) })
+    }
+
+    /// A redirect policy checking every hop against this policy.
+    pub fn redirect_policy(self: &Arc<Self>, max_redirects: usize) -> redirect::Policy {
+        let policy = Arc::clone(self);
+        redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() > max_redirects {
+                return attempt.error(CrawlerError::TooManyRedirects(max_redirects));
+            }
+            match policy.check_url(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => {
+                    warn!("Not following redirect to {}: {}", attempt.url(), e);
+                    attempt.error(e)
+                }
+            }
+        })
+    }
+}
+
+/// Resolves host names with the system resolver and keeps only the
+/// addresses allowed by a [`NetworkPolicy`]. A name without any allowed
+/// address fails to resolve with [`CrawlerError::Blocked`].
+pub struct PolicyResolver {
+    policy: Arc<NetworkPolicy>,
+}
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = Arc::clone(&self.policy);
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            let allowed: Vec<SocketAddr> = if policy.is_allowed_host(&host) {
+                addresses
+            } else {
+                addresses.into_iter().filter(|address| policy.is_allowed_address(address.ip())).collect()
+            };
+            if allowed.is_empty() {
+                warn!("Refusing to connect to {}: no public address", host);
+                return Err(CrawlerError::Blocked(format!("{} resolves to non-public addresses only", host)).into());
+            }
+            Ok(Box::new(allowed.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether an address belongs to the public internet. IPv4 addresses
+/// embedded in IPv6 ones (mapped, NAT64, 6to4) are judged by the IPv4 part.
+pub fn is_public_address(address: IpAddr) -> bool {
+    let address = match address {
+        IpAddr::V6(v6) => embedded_ipv4(v6).map_or(address, IpAddr::V4),
+        IpAddr::V4(_) => address,
+    };
+    !BLOCKED_NETWORKS.iter().any(|network| network.contains(&address))
+}
+
+fn embedded_ipv4(address: Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(v4) = address.to_ipv4_mapped() {
+        return Some(v4);
+    }
+    let octets = address.octets();
+    match address.segments() {
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => Some(Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15])),
+        [0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+        _ => None,
+    }
+}
+
+/// The network policy violation a request failed with, if that is why it
+/// failed. Violations found by the resolver or the redirect policy reach the
+/// caller wrapped in client errors.
+pub fn blocked_reason(error: &(dyn Error + 'static)) -> Option<String> {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(CrawlerError::Blocked(reason)) = error.downcast_ref::<CrawlerError>() {
+            return Some(reason.clone());
+        }
+        source = error.source();
+    }
+    None
+}
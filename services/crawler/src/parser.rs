@@ -5,6 +5,12 @@ use sha2::{Sha256, Digest};
 use chrono::{DateTime, Utc};
 use serde_json::{Value, json};
 use log::warn;
+use crate::content_extractor::{self, ExtractedContent};
+use crate::document_date;
+use crate::structured_data;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
 
 #[derive(Error, Debug)]
 pub enum ParserError {
@@ -13,6 +19,28 @@ pub enum ParserError {
 
     #[error("Failed to parse selector: {0}")]
     SelectorParseError(#[from] scraper::error::SelectorErrorKind<'static>),
+
+    #[error("Failed to parse PDF: {0}")]
+    InvalidPdf(String),
+}
+
+/// Longest title taken from the first line of a document without one.
+const MAX_DOCUMENT_TITLE_CHARS: usize = 200;
+
+/// Pages with less main text than this are stored without content.
+const MIN_CONTENT_WORDS: usize = 30;
+
+/// Most headings kept in a page's outline.
+const MAX_OUTLINE_HEADINGS: usize = 50;
+
+/// The `rel` keywords kept with a link.
+const LINK_RELS: &[&str] = &["nofollow", "ugc", "sponsored", "next", "prev"];
+
+lazy_static! {
+    static ref LINK_SELECTOR: Selector = Selector::parse("link[rel][href]").unwrap();
+    static ref META_NAME_SELECTOR: Selector = Selector::parse("meta[name][content]").unwrap();
+    static ref LINK_SOURCE_SELECTOR: Selector = Selector::parse("a[href], area[href], iframe[src], link[rel][href]").unwrap();
+    static ref BASE_SELECTOR: Selector = Selector::parse("base[href]").unwrap();
 }
 
 /// Represents a parsed webpage with all its extracted information
@@ -42,11 +70,125 @@ pub struct ParsedWebpage {
     pub meta_title: Option<String>,
     pub meta_description: Option<String>,
     pub meta_keywords: Option<String>,
+    /// Absolute URL from `<link rel="canonical">`
+    pub canonical_url: Option<String>,
+    /// Directives from `<meta name="robots">`
+    pub robots: RobotsDirectives,
+}
+
+/// Indexing directives a page gives crawlers, through `<meta name="robots">`
+/// or the `X-Robots-Tag` response header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RobotsDirectives {
+    /// The page must not be stored or indexed
+    pub noindex: bool,
+    /// The page's links must not be followed
+    pub nofollow: bool,
+}
+
+impl RobotsDirectives {
+    /// Parses a comma separated directive list such as `noindex, nofollow`.
+    pub fn parse(value: &str) -> Self {
+        let mut directives = RobotsDirectives::default();
+        for directive in value.split(',').map(|d| d.trim().to_ascii_lowercase()) {
+            match directive.as_str() {
+                "noindex" => directives.noindex = true,
+                "nofollow" => directives.nofollow = true,
+                "none" => {
+                    directives.noindex = true;
+                    directives.nofollow = true;
+                }
+                _ => {}
+            }
+        }
+        directives
+    }
+
+    /// Parses the values of `X-Robots-Tag` headers. A value can be aimed at a
+    /// single crawler with a prefix (`otherbot: noindex`); values for other
+    /// crawlers than `user_agent` are ignored.
+    pub fn from_header_values(values: &[String], user_agent: &str) -> Self {
+        let our_token = user_agent.split('/').next().unwrap_or(user_agent).trim().to_ascii_lowercase();
+        values.iter().fold(RobotsDirectives::default(), |directives, value| {
+            let scoped = value.split_once(':').filter(|(name, _)| {
+                let name = name.trim().to_ascii_lowercase();
+                // Some directives carry a value after a colon themselves
+                !name.contains(',') && !name.starts_with("unavailable_after") && !name.starts_with("max-")
+            });
+            match scoped {
+                Some((name, rest)) if name.trim().eq_ignore_ascii_case(&our_token) => directives.merge(Self::parse(rest)),
+                Some(_) => directives,
+                None => directives.merge(Self::parse(value)),
+            }
+        })
+    }
+
+    pub fn merge(self, other: Self) -> Self {
+        RobotsDirectives {
+            noindex: self.noindex || other.noindex,
+            nofollow: self.nofollow || other.nofollow,
+        }
+    }
 }
 
 pub struct ParsedLink {
     pub target_url: String,
     pub anchor_text: Option<String>,
+    /// `rel` keywords of the link that matter to crawling and ranking
+    /// (nofollow, ugc, sponsored, next, prev), lowercased
+    pub rel: Vec<String>,
+    pub kind: LinkKind,
+    pub position: LinkPosition,
+    /// Whether the link stays on the page's host
+    pub is_internal: bool,
+}
+
+impl ParsedLink {
+    /// Links marked `nofollow`, `ugc` or `sponsored` carry no endorsement
+    /// and are not followed.
+    pub fn is_followable(&self) -> bool {
+        !self.rel.iter().any(|rel| matches!(rel.as_str(), "nofollow" | "ugc" | "sponsored"))
+    }
+}
+
+/// The element a link was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Anchor,
+    Area,
+    Iframe,
+    /// `<link rel="next">` or `<link rel="prev">`
+    Link,
+}
+
+impl LinkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::Anchor => "a",
+            LinkKind::Area => "area",
+            LinkKind::Iframe => "iframe",
+            LinkKind::Link => "link",
+        }
+    }
+}
+
+/// The part of the page a link sits in. Body links are editorial, the
+/// others are repeated on every page of a site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkPosition {
+    Body,
+    Navigation,
+    Footer,
+}
+
+impl LinkPosition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkPosition::Body => "body",
+            LinkPosition::Navigation => "nav",
+            LinkPosition::Footer => "footer",
+        }
+    }
 }
 
 pub fn parse_webpage(html: &str, url: &str, status: i32) -> Result<ParsedWebpage, ParserError> {
@@ -55,12 +197,24 @@ pub fn parse_webpage(html: &str, url: &str, status: i32) -> Result<ParsedWebpage
     let domain = parsed_url.domain().unwrap_or("").to_string();
 
     let title = extract_title(&document);
-    let content = extract_content(&document); // This is still full content, to be summarized later
-    let links = extract_links(&document, &parsed_url, true)?;
+    let extracted = content_extractor::extract(&document);
+    let content = extract_content(&extracted); // This is still full content, to be summarized later
+    let robots = extract_robots_directives(&document);
+    let links = if robots.nofollow {
+        Vec::new()
+    } else {
+        extract_links(&document, &parsed_url)
+    };
     let content_hash = calculate_hash(html);
-    let (meta_title, meta_description, meta_keywords, other_metadata) = extract_metadata(&document);
+    let (meta_title, meta_description, meta_keywords, mut other_metadata) = extract_metadata(&document, &parsed_url);
+    if !extracted.headings.is_empty() {
+        let outline = &extracted.headings[..extracted.headings.len().min(MAX_OUTLINE_HEADINGS)];
+        other_metadata.get_or_insert_with(|| json!({}))["headings"] = json!(outline);
+    }
+    let canonical_url = extract_canonical_url(&document, &parsed_url);
+    let date = document_date::from_page(other_metadata.as_ref(), &document);
 
-    Ok(ParsedWebpage {
+    let mut page = ParsedWebpage {
         url: url.to_string(),
         domain,
         title,
@@ -74,55 +228,275 @@ pub fn parse_webpage(html: &str, url: &str, status: i32) -> Result<ParsedWebpage
         meta_title,
         meta_description,
         meta_keywords,
+        canonical_url,
+        robots,
+    };
+    if let Some(date) = date {
+        date.apply_to(&mut page);
+    }
+    Ok(page)
+}
+
+/// Extracts a PDF document. Title and author come from the document's info
+/// dictionary; without a title the first line of text is used instead.
+pub fn parse_pdf(body: &[u8], url: &str, status: i32) -> Result<ParsedWebpage, ParserError> {
+    let parsed_url = Url::parse(url)?;
+
+    let mut document = pdf_extract::Document::load_mem(body).map_err(|e| ParserError::InvalidPdf(e.to_string()))?;
+    if document.is_encrypted() {
+        // Most "encrypted" PDFs only restrict printing and open with an empty password
+        document.decrypt("").map_err(|e| ParserError::InvalidPdf(e.to_string()))?;
+    }
+
+    let page_count = document.get_pages().len();
+    let info_title = pdf_info_string(&document, b"Title");
+    let author = pdf_info_string(&document, b"Author");
+    let subject = pdf_info_string(&document, b"Subject");
+    let keywords = pdf_info_string(&document, b"Keywords");
+    let date = pdf_info_string(&document, b"ModDate")
+        .and_then(|value| document_date::from_pdf_info(&value))
+        .or_else(|| pdf_info_string(&document, b"CreationDate").and_then(|value| document_date::from_pdf_info(&value)));
+
+    // The text extraction panics on some malformed documents. The document is
+    // not used after a panic, so it is safe to unwind through.
+    let text = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut text = String::new();
+        pdf_extract::output_doc(&document, &mut pdf_extract::PlainTextOutput::new(&mut text)).map(|_| text)
+    }))
+    .map_err(|_| ParserError::InvalidPdf("text extraction panicked".to_string()))?
+    .map_err(|e| ParserError::InvalidPdf(e.to_string()))?;
+
+    let mut metadata = json!({ "document_type": "pdf", "page_count": page_count });
+    if let Some(ref author) = author {
+        metadata["author"] = json!(author);
+    }
+
+    let mut page = ParsedWebpage {
+        url: url.to_string(),
+        domain: parsed_url.domain().unwrap_or("").to_string(),
+        title: info_title.clone().or_else(|| first_line(&text)),
+        content: normalize_document_text(&text),
+        fetch_timestamp: Utc::now(),
+        last_updated_timestamp: None,
+        status: Some(status),
+        content_hash: calculate_hash(body),
+        metadata: Some(metadata),
+        links: Vec::new(),
+        meta_title: info_title,
+        meta_description: subject,
+        meta_keywords: keywords,
+        canonical_url: None,
+        robots: RobotsDirectives::default(),
+    };
+    if let Some(date) = date {
+        date.apply_to(&mut page);
+    }
+    Ok(page)
+}
+
+/// Extracts a `text/plain` document, titled by its first line.
+pub fn parse_plain_text(text: &str, url: &str, status: i32) -> Result<ParsedWebpage, ParserError> {
+    let parsed_url = Url::parse(url)?;
+
+    Ok(ParsedWebpage {
+        url: url.to_string(),
+        domain: parsed_url.domain().unwrap_or("").to_string(),
+        title: first_line(text),
+        content: normalize_document_text(text),
+        fetch_timestamp: Utc::now(),
+        last_updated_timestamp: None,
+        status: Some(status),
+        content_hash: calculate_hash(text),
+        metadata: Some(json!({ "document_type": "text" })),
+        links: Vec::new(),
+        meta_title: None,
+        meta_description: None,
+        meta_keywords: None,
+        canonical_url: None,
+        robots: RobotsDirectives::default(),
     })
 }
 
-pub fn extract_links(parsed_html: &Html, base_url: &Url, respect_nofollow: bool) -> Result<Vec<ParsedLink>, ParserError> {
-    let selector = Selector::parse("a[href]").map_err(|e| ParserError::SelectorParseError(e))?;
-    let mut links = Vec::new();
-    let mut seen_urls = std::collections::HashSet::new();
+/// Whether the page was extracted from a document rather than HTML.
+pub fn is_document(page: &ParsedWebpage) -> bool {
+    page.metadata.as_ref().is_some_and(|m| m.get("document_type").is_some())
+}
+
+fn pdf_info_string(document: &pdf_extract::Document, key: &[u8]) -> Option<String> {
+    let info = document.trailer.get(b"Info").ok()?;
+    let (_, info) = document.dereference(info).ok()?;
+    let value = info.as_dict().ok()?.get(key).ok()?;
+    let (_, value) = document.dereference(value).ok()?;
+    pdf_extract::decode_text_string(value)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
 
-    for element in parsed_html.select(&selector) {
-        if let Some(href) = element.value().attr("href") {
-            // Check for nofollow if configured
-            if respect_nofollow {
-                if let Some(rel) = element.value().attr("rel") {
-                    if rel.contains("nofollow") {
-                        continue;
-                    }
-                }
+fn first_line(text: &str) -> Option<String> {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| line.chars().take(MAX_DOCUMENT_TITLE_CHARS).collect())
+}
+
+fn normalize_document_text(text: &str) -> Option<String> {
+    let content = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    Some(content).filter(|s| !s.is_empty())
+}
+
+/// Collects the outgoing links of a page: anchors, image map areas, iframes
+/// and `<link rel="next|prev">` pagination, resolved against the page's
+/// `<base href>`. A URL linked several times is kept once, preferring an
+/// occurrence in the body over navigation and footer ones.
+pub fn extract_links(parsed_html: &Html, page_url: &Url) -> Vec<ParsedLink> {
+    let base_url = document_base_url(parsed_html, page_url);
+    let mut links: Vec<ParsedLink> = Vec::new();
+    let mut seen_urls: HashMap<String, usize> = HashMap::new();
+
+    for element in parsed_html.select(&LINK_SOURCE_SELECTOR) {
+        let el = element.value();
+        let (kind, target) = match el.name() {
+            "a" => (LinkKind::Anchor, el.attr("href")),
+            "area" => (LinkKind::Area, el.attr("href")),
+            "iframe" => (LinkKind::Iframe, el.attr("src")),
+            _ if has_rel(el, "next") || has_rel(el, "prev") => (LinkKind::Link, el.attr("href")),
+            _ => continue,
+        };
+        let Some(target) = target.map(str::trim).filter(|t| !t.is_empty()) else {
+            continue;
+        };
+
+        // Resolve relative URLs
+        let absolute_url = match base_url.join(target) {
+            Ok(absolute_url) => absolute_url,
+            Err(e) => {
+                warn!("Failed to resolve URL '{}': {}", target, e);
+                continue;
             }
-            
-            // Resolve relative URLs
-            match base_url.join(href) {
-                Ok(absolute_url) => {
-                    let url_str = absolute_url.to_string();
-                    if !seen_urls.contains(&url_str) {
-                        seen_urls.insert(url_str.clone());
-                        
-                        let anchor_text = element.text()
-                            .collect::<String>()
-                            .trim()
-                            .to_string();
-                        
-                        let final_text = match (anchor_text.is_empty(), element.value().attr("title")) {
-                            (false, _) => anchor_text,
-                            (true, Some(title)) => title.to_string(),
-                            (true, None) => String::new()
-                        };
-
-                        links.push(ParsedLink {
-                            target_url: url_str,
-                            anchor_text: Some(final_text).filter(|s| !s.is_empty()),
-                        });
-                    }
-                }
-                Err(e) => warn!("Failed to resolve URL '{}': {}", href, e),
+        };
+        if !matches!(absolute_url.scheme(), "http" | "https") {
+            continue;
+        }
+
+        let text = match kind {
+            LinkKind::Anchor => Some(element.text().collect::<String>()),
+            LinkKind::Area => el.attr("alt").map(str::to_string),
+            LinkKind::Iframe | LinkKind::Link => None,
+        };
+        let anchor_text = text
+            .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|text| !text.is_empty())
+            .or_else(|| el.attr("title").map(|title| title.trim().to_string()))
+            .filter(|text| !text.is_empty());
+
+        let rel = el.attr("rel")
+            .map(|rel| rel.split_whitespace()
+                .map(str::to_ascii_lowercase)
+                .filter(|value| LINK_RELS.contains(&value.as_str()))
+                .collect())
+            .unwrap_or_default();
+        let position = if kind == LinkKind::Link { LinkPosition::Navigation } else { link_position(element) };
+
+        let link = ParsedLink {
+            is_internal: same_host(page_url, &absolute_url),
+            target_url: absolute_url.to_string(),
+            anchor_text,
+            rel,
+            kind,
+            position,
+        };
+        match seen_urls.get(&link.target_url) {
+            Some(&i) if links[i].position != LinkPosition::Body && link.position == LinkPosition::Body => links[i] = link,
+            Some(_) => {}
+            None => {
+                seen_urls.insert(link.target_url.clone(), links.len());
+                links.push(link);
             }
         }
     }
 
-    Ok(links)
+    links
+}
+
+/// The URL relative links of the page resolve against: its `<base href>`,
+/// if it has a usable one.
+fn document_base_url(parsed_html: &Html, page_url: &Url) -> Url {
+    parsed_html.select(&BASE_SELECTOR)
+        .next()
+        .and_then(|base| base.value().attr("href"))
+        .and_then(|href| page_url.join(href.trim()).ok())
+        .filter(|base| matches!(base.scheme(), "http" | "https"))
+        .unwrap_or_else(|| page_url.clone())
+}
+
+/// Where a link sits, from the closest sectioning element around it. A
+/// `<header>` inside an article holds its title and byline, not navigation.
+fn link_position(element: ElementRef) -> LinkPosition {
+    let mut ancestors = element.ancestors().filter_map(ElementRef::wrap);
+    while let Some(ancestor) = ancestors.next() {
+        let el = ancestor.value();
+        let role = el.attr("role").unwrap_or("").trim();
+        match (el.name(), role) {
+            ("footer", _) | (_, "contentinfo") => return LinkPosition::Footer,
+            ("nav" | "aside", _) | (_, "navigation" | "complementary" | "banner") => return LinkPosition::Navigation,
+            ("article" | "main", _) | (_, "main" | "article") => return LinkPosition::Body,
+            ("header", _) => {
+                let in_content = ancestors.any(|a| matches!(a.value().name(), "article" | "main"));
+                return if in_content { LinkPosition::Body } else { LinkPosition::Navigation };
+            }
+            _ => {}
+        }
+    }
+    LinkPosition::Body
+}
+
+fn has_rel(element: &scraper::node::Element, rel: &str) -> bool {
+    element.attr("rel").is_some_and(|value| value.split_whitespace().any(|v| v.eq_ignore_ascii_case(rel)))
+}
+
+/// Whether two URLs are on the same host, counting `www.` as the same site.
+fn same_host(a: &Url, b: &Url) -> bool {
+    let host = |url: &Url| url.host_str().map(|h| h.trim_start_matches("www.").to_ascii_lowercase());
+    host(a).is_some() && host(a) == host(b)
+}
+
+/// `<link>` elements whose space separated `rel` contains `rel`.
+fn link_elements<'a>(parsed_html: &'a Html, rel: &'a str) -> impl Iterator<Item = ElementRef<'a>> + 'a {
+    parsed_html
+        .select(&LINK_SELECTOR)
+        .filter(move |element| {
+            element.value().attr("rel")
+                .is_some_and(|value| value.split_whitespace().any(|r| r.eq_ignore_ascii_case(rel)))
+        })
+}
+
+fn extract_canonical_url(parsed_html: &Html, base_url: &Url) -> Option<String> {
+    link_elements(parsed_html, "canonical")
+        .filter_map(|element| element.value().attr("href"))
+        .filter_map(|href| base_url.join(href.trim()).ok())
+        .find(|url| url.scheme() == "http" || url.scheme() == "https")
+        .map(|url| url.to_string())
+}
+
+/// `<link rel="alternate" hreflang>` variants of the page, as
+/// `{"hreflang": .., "url": ..}` objects.
+fn extract_language_alternates(parsed_html: &Html, base_url: &Url) -> Vec<Value> {
+    link_elements(parsed_html, "alternate")
+        .filter_map(|element| {
+            let hreflang = element.value().attr("hreflang")?.trim();
+            let url = base_url.join(element.value().attr("href")?.trim()).ok()?;
+            Some(json!({ "hreflang": hreflang.to_lowercase(), "url": url.to_string() }))
+        })
+        .collect()
+}
+
+fn extract_robots_directives(parsed_html: &Html) -> RobotsDirectives {
+    parsed_html
+        .select(&META_NAME_SELECTOR)
+        .filter(|element| element.value().attr("name").is_some_and(|name| name.trim().eq_ignore_ascii_case("robots")))
+        .filter_map(|element| element.value().attr("content"))
+        .fold(RobotsDirectives::default(), |directives, content| directives.merge(RobotsDirectives::parse(content)))
 }
 
 fn extract_title(parsed_html: &Html) -> Option<String> {
@@ -133,116 +507,34 @@ fn extract_title(parsed_html: &Html) -> Option<String> {
         .filter(|s| !s.is_empty())
 }
 
-fn extract_content(parsed_html: &Html) -> Option<String> {
-    let mut content = String::new();
-
-    // Priority areas that typically contain main content
-    let content_selectors = [
-        // Main content containers
-        "article", "main", "[role='main']", ".main-content", "#main-content",
-        // Blog and article specific
-        ".post-content", ".article-content", ".entry-content", ".content",
-        // Documentation specific
-        ".documentation", ".docs-content", ".wiki-content",
-        // Generic content areas
-        "#content", ".content-area", "[itemprop='articleBody']",
-        // Fallback to any large text container
-        ".text-content", ".body-content"
-    ];
-
-    // Elements that usually contain noise
-    let noise_selector = Selector::parse(concat!(
-        "header, footer, nav, aside, .sidebar, .comments, .advertisement,",
-        ".share-buttons, .social-media, .related-posts, .recommended,",
-        ".navigation, .menu, .search, .popup, .modal, script, style,",
-        "[role='complementary'], [role='banner'], [role='contentinfo'],",
-        ".cookie-notice, .newsletter-signup, .subscription-box,",
-        "#comments, .comments-area, .widget-area"
-    )).unwrap_or_else(|_| Selector::parse("script, style").unwrap());
-
-    // First try to find main content areas
-    for selector in content_selectors.iter() {
-        if let Ok(sel) = Selector::parse(selector) {
-            if let Some(element) = parsed_html.select(&sel).next() {
-                // Remove noise elements
-                let mut html = element.html();
-                for el in parsed_html.select(&noise_selector) {
-                    html = html.replace(&el.html(), "");
-                }
-                
-                // Parse the cleaned HTML
-                let fragment = Html::parse_fragment(&html);
-                let text = fragment.root_element()
-                    .text()
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                
-                // If we found substantial content, use it
-                if text.split_whitespace().count() > 50 {
-                    content = text;
-                    break;
-                }
-            }
-        }
-    }
+/// The main text of the page, if there is enough of it to be worth storing.
+fn extract_content(extracted: &ExtractedContent) -> Option<String> {
+    Some(extracted.text.clone()).filter(|text| count_words(text) >= MIN_CONTENT_WORDS)
+}
 
-    // If no content found in priority areas, try extracting from body
-    // but with smarter content detection
-    if content.is_empty() {
-        if let Ok(body_selector) = Selector::parse("body") {
-            if let Some(body) = parsed_html.select(&body_selector).next() {
-                // Remove noise first
-                let mut html = body.html();
-                for el in parsed_html.select(&noise_selector) {
-                    html = html.replace(&el.html(), "");
-                }
-                
-                let fragment = Html::parse_fragment(&html);
-                
-                // Find paragraphs and other text-heavy elements
-                let text_selectors = [
-                    "p", "article", "section", "div > p",
-                    "[class*='text']", "[class*='content']",
-                    "h1 ~ p", "h2 ~ p", "h3 ~ p"
-                ];
-                
-                let mut text_blocks = Vec::new();
-                
-                for selector in text_selectors.iter() {
-                    if let Ok(sel) = Selector::parse(selector) {
-                        for element in fragment.select(&sel) {
-                            let text = element.text().collect::<String>();
-                            // Only include blocks with substantial text
-                            if text.split_whitespace().count() > 20 {
-                                text_blocks.push(text);
-                            }
-                        }
-                    }
-                }
-                
-                content = text_blocks.join(" ");
-            }
-        }
-    }
+/// Counts words for the content length thresholds. Scripts written without
+/// spaces between words (Chinese, Japanese) count every character as a word,
+/// so their pages are not taken for nearly empty ones.
+pub(crate) fn count_words(text: &str) -> usize {
+    text.split_whitespace()
+        .map(|word| {
+            let ideographs = word.chars().filter(|c| is_unspaced_script(*c)).count();
+            if ideographs > 0 { ideographs } else { 1 }
+        })
+        .sum()
+}
 
-    // Clean up the content
-    let content = content
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ")
-        .replace(|c: char| !c.is_alphanumeric() && !c.is_whitespace() && c != '.' && c != ',' && c != '?' && c != '!', " ")
-        .replace("  ", " ")
-        .trim()
-        .to_string();
-
-    if content.split_whitespace().count() < 30 {
-        None
-    } else {
-        Some(content)
-    }
+fn is_unspaced_script(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // Hiragana, Katakana
+        | 0x3400..=0x4DBF   // CJK Extension A
+        | 0x4E00..=0x9FFF   // CJK Unified Ideographs
+        | 0xF900..=0xFAFF   // CJK Compatibility Ideographs
+        | 0x20000..=0x2FFFF // CJK Extensions B and later
+    )
 }
 
-fn calculate_hash(content: &str) -> String {
+fn calculate_hash(content: impl AsRef<[u8]>) -> String {
     let mut hasher = Sha256::new();
     hasher.update(content);
     let result = hasher.finalize();
@@ -256,7 +548,7 @@ fn extract_meta_content<'a>(element: &'a ElementRef<'a>) -> Option<&'a str> {
         .or_else(|| element.text().next())
 }
 
-fn extract_metadata(document: &Html) -> (Option<String>, Option<String>, Option<String>, Option<Value>) {
+fn extract_metadata(document: &Html, base_url: &Url) -> (Option<String>, Option<String>, Option<String>, Option<Value>) {
     let meta_selectors = [
         ("meta[name='title'], meta[property='og:title']", "content"),
         ("meta[name='description'], meta[property='og:description']", "content"),
@@ -284,19 +576,25 @@ fn extract_metadata(document: &Html) -> (Option<String>, Option<String>, Option<
         }
     }
 
-    let other_metadata = extract_other_metadata(document);
+    let other_metadata = extract_other_metadata(document, base_url);
 
     (title, description, keywords, other_metadata)
 }
 
-fn extract_other_metadata(document: &Html) -> Option<Value> {
+fn extract_other_metadata(document: &Html, base_url: &Url) -> Option<Value> {
     let mut metadata = json!({});
     if let Some(lang) = document.select(&Selector::parse("html[lang]").unwrap()).next() {
         if let Some(lang_value) = lang.value().attr("lang") {
             metadata["language"] = json!(lang_value);
         }
     }
-    // Add more metadata extraction as needed
+    let alternates = extract_language_alternates(document, base_url);
+    if !alternates.is_empty() {
+        metadata["alternates"] = json!(alternates);
+    }
+    for (key, value) in structured_data::extract(document) {
+        metadata[key.as_str()] = value;
+    }
     if metadata.as_object().unwrap().is_empty() {
         None
     } else {
@@ -0,0 +1,200 @@
+use std::fmt;
+use regex::{Regex, RegexBuilder};
+use crate::config::{Config, QualityConfig};
+use crate::parser::{self, ParsedWebpage};
+
+/// Words and phrases typical of spam pages, matched case-insensitively as
+/// whole words.
+pub const DEFAULT_SPAM_KEYWORDS: &[&str] = &[
+    "casino bonus", "online casino", "viagra", "cialis", "payday loan", "replica watches",
+    "buy followers", "buy backlinks", "essay writing service", "crypto giveaway",
+    "free spins", "weight loss pills", "work from home and earn",
+];
+
+/// Spam density is measured against at least this many words, so that a
+/// single keyword on a short page does not count as stuffing.
+const MIN_SPAM_SAMPLE_WORDS: usize = 100;
+
+/// Value of the language signal for pages that do not declare a language.
+const UNKNOWN_LANGUAGE_VALUE: f64 = 0.5;
+
+/// What a [`QualityScorer`] judges a page by.
+pub struct PageSignals<'a> {
+    pub page: &'a ParsedWebpage,
+    /// Size of the fetched body in bytes
+    pub body_size: usize,
+    /// Whether another stored page is a near-duplicate of this one
+    pub is_duplicate: bool,
+}
+
+/// Points a page earned for one signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalScore {
+    pub name: &'static str,
+    pub points: f64,
+    pub max_points: f64,
+}
+
+/// A page's quality score, kept per signal so that decisions can be
+/// explained.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QualityScore {
+    pub signals: Vec<SignalScore>,
+}
+
+impl QualityScore {
+    /// Records a signal worth up to `weight` points, of which the page earns
+    /// the share `value` (clamped to 0..=1).
+    pub fn add(&mut self, name: &'static str, weight: f64, value: f64) {
+        self.signals.push(SignalScore {
+            name,
+            points: weight * value.clamp(0.0, 1.0),
+            max_points: weight,
+        });
+    }
+
+    pub fn total(&self) -> f64 {
+        self.signals.iter().map(|signal| signal.points).sum()
+    }
+}
+
+impl fmt::Display for QualityScore {
+    /// The per-signal breakdown, as in `title 5.0/5, links 8.4/20`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, signal) in self.signals.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {:.1}/{}", signal.name, signal.points, signal.max_points)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rates how worth storing a parsed page is. Pages scoring below the
+/// configured `min_quality_score` are not stored.
+pub trait QualityScorer: Send + Sync {
+    fn score(&self, signals: &PageSignals) -> QualityScore;
+}
+
+/// The default scorer: a weighted sum of signals, with weights and thresholds
+/// from the `[quality]` section of the configuration.
+///
+/// Every signal is worth between zero and its weight: the length of the main
+/// content, the presence of title, meta description, meta keywords and
+/// structured metadata, the number of links, a priority domain, the ratio of
+/// text to HTML, the declared language, not being a near-duplicate of another
+/// page and the absence of spam keywords. Documents (PDF, plain text) have no
+/// links and no markup, so their length also carries the links' weight and
+/// their text ratio counts as full.
+pub struct WeightedQualityScorer {
+    config: QualityConfig,
+    priority_domains: Vec<String>,
+    spam_pattern: Option<Regex>,
+}
+
+impl WeightedQualityScorer {
+    pub fn new(config: QualityConfig, priority_domains: Vec<String>) -> Self {
+        let spam_pattern = spam_pattern(&config.spam_keywords);
+        WeightedQualityScorer {
+            config,
+            priority_domains,
+            spam_pattern,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.quality.clone(), config.priority_domains.clone().unwrap_or_default())
+    }
+
+    fn language_value(&self, page: &ParsedWebpage) -> f64 {
+        if self.config.languages.is_empty() {
+            return 1.0;
+        }
+        let declared = page.metadata.as_ref()
+            .and_then(|metadata| metadata.get("language"))
+            .and_then(|language| language.as_str())
+            .and_then(|language| language.split(['-', '_']).next())
+            .map(|language| language.trim().to_lowercase())
+            .filter(|language| !language.is_empty());
+        match declared {
+            Some(language) if self.config.languages.iter().any(|wanted| wanted.eq_ignore_ascii_case(&language)) => 1.0,
+            Some(_) => 0.0,
+            None => UNKNOWN_LANGUAGE_VALUE,
+        }
+    }
+
+    fn text_ratio_value(&self, signals: &PageSignals, is_document: bool) -> f64 {
+        if is_document {
+            return 1.0;
+        }
+        let text_size = signals.page.content.as_deref().map_or(0, str::len);
+        let ratio = text_size as f64 / signals.body_size.max(1) as f64;
+        let (min, full) = (self.config.min_text_ratio, self.config.full_text_ratio);
+        if full <= min {
+            return if ratio >= full { 1.0 } else { 0.0 };
+        }
+        (ratio - min) / (full - min)
+    }
+
+    /// 1.0 for pages free of spam keywords, falling to 0.0 at
+    /// `max_spam_density` hits per 1000 words.
+    fn spam_value(&self, page: &ParsedWebpage, word_count: usize) -> f64 {
+        let Some(ref pattern) = self.spam_pattern else {
+            return 1.0;
+        };
+        let hits: usize = [page.title.as_deref(), page.content.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(|text| pattern.find_iter(text).count())
+            .sum();
+        if hits == 0 {
+            return 1.0;
+        }
+        let density = hits as f64 * 1000.0 / word_count.max(MIN_SPAM_SAMPLE_WORDS) as f64;
+        1.0 - density / self.config.max_spam_density.max(f64::EPSILON)
+    }
+}
+
+impl QualityScorer for WeightedQualityScorer {
+    fn score(&self, signals: &PageSignals) -> QualityScore {
+        let page = signals.page;
+        let weights = &self.config.weights;
+        let is_document = parser::is_document(page);
+        let word_count = page.content.as_deref().map_or(0, parser::count_words);
+        let present = |field: bool| if field { 1.0 } else { 0.0 };
+
+        let mut score = QualityScore::default();
+        let content_weight = if is_document { weights.content_length + weights.links } else { weights.content_length };
+        score.add("content_length", content_weight, word_count as f64 / self.config.full_content_words.max(1) as f64);
+        score.add("title", weights.title, present(page.title.is_some()));
+        score.add("meta_description", weights.meta_description, present(page.meta_description.is_some()));
+        score.add("meta_keywords", weights.meta_keywords, present(page.meta_keywords.is_some()));
+        score.add("metadata", weights.metadata, present(page.metadata.is_some()));
+        if !is_document {
+            score.add("links", weights.links, page.links.len() as f64 / self.config.full_link_count.max(1) as f64);
+        }
+        let is_priority = self.priority_domains.iter().any(|domain| page.domain.contains(domain.as_str()));
+        score.add("priority_domain", weights.priority_domain, present(is_priority));
+        score.add("text_ratio", weights.text_ratio, self.text_ratio_value(signals, is_document));
+        score.add("language", weights.language, self.language_value(page));
+        score.add("uniqueness", weights.uniqueness, present(!signals.is_duplicate));
+        score.add("no_spam", weights.no_spam, self.spam_value(page, word_count));
+        score
+    }
+}
+
+/// One case-insensitive whole-word pattern matching any of the keywords.
+fn spam_pattern(keywords: &[String]) -> Option<Regex> {
+    let alternatives: Vec<String> = keywords.iter()
+        .map(|keyword| keyword.split_whitespace().map(regex::escape).collect::<Vec<_>>().join(r"\s+"))
+        .filter(|keyword| !keyword.is_empty())
+        .collect();
+    if alternatives.is_empty() {
+        return None;
+    }
+    RegexBuilder::new(&format!(r"\b(?:{})\b", alternatives.join("|")))
+        .case_insensitive(true)
+        .build()
+        .ok()
+}
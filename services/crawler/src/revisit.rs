@@ -0,0 +1,77 @@
+use crate::config::Config;
+use std::time::Duration;
+use storage::FetchHistory;
+
+/// How much a page's rank can shorten its revisit interval. The page with the
+/// highest rank is revisited `1 + RANK_WEIGHT` times as often as an unranked
+/// page with the same change history.
+const RANK_WEIGHT: f64 = 1.0;
+
+/// Decides when a known page should be fetched again.
+///
+/// The change rate of a page is estimated from how many of its fetches saw a
+/// different `content_hash` than the one before. Pages that change on every
+/// fetch are revisited after `min_interval`, pages that never change drift
+/// towards `max_interval`, on a logarithmic scale in between. Important pages
+/// (by `page_rank`, relative to the best ranked page) are revisited sooner.
+pub struct RevisitPolicy {
+    min_interval: Duration,
+    max_interval: Duration,
+}
+
+impl RevisitPolicy {
+    pub fn new(min_interval: Duration, max_interval: Duration) -> Self {
+        RevisitPolicy {
+            min_interval,
+            max_interval: max_interval.max(min_interval),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            Duration::from_secs(config.min_revisit_hours * 3600),
+            Duration::from_secs(config.max_revisit_hours * 3600),
+        )
+    }
+
+    /// Delay before retrying a page whose revisit failed.
+    pub fn min_interval(&self) -> Duration {
+        self.min_interval
+    }
+
+    /// Delay for pages that should be left alone for as long as possible.
+    pub fn max_interval(&self) -> Duration {
+        self.max_interval
+    }
+
+    /// Estimated probability that the page changed between two fetches.
+    ///
+    /// One change is assumed up front, so a page seen only once starts in the
+    /// middle of the range instead of at either end.
+    pub fn change_rate(history: &FetchHistory) -> f64 {
+        let fetches = history.fetch_count.max(0) as f64;
+        let changes = (history.change_count.max(0) as f64).min(fetches);
+        (changes + 1.0) / (fetches + 1.0)
+    }
+
+    /// Time until the next fetch of a page with the given history.
+    /// `max_page_rank` is the highest rank of any stored page.
+    pub fn next_interval(&self, history: &FetchHistory, max_page_rank: f64) -> Duration {
+        let min = self.min_interval.as_secs_f64();
+        let max = self.max_interval.as_secs_f64();
+        if min <= 0.0 {
+            return self.min_interval;
+        }
+
+        let base = min * (max / min).powf(1.0 - Self::change_rate(history));
+
+        let relative_rank = if max_page_rank > 0.0 {
+            (history.page_rank / max_page_rank).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let interval = base / (1.0 + RANK_WEIGHT * relative_rank);
+
+        Duration::from_secs_f64(interval.clamp(min, max))
+    }
+}
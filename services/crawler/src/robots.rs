@@ -1,73 +1,179 @@
 //robots.rs
 
 use reqwest::Client;
-use robotxt::Robots;
+use robotxt::{AccessResult, Robots};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
 use url::Url;
 use log::{info, warn};
+use crate::network::{self, NetworkPolicy};
 
+/// How long a successfully fetched (or 4xx "unavailable") robots.txt is reused.
+/// RFC 9309 asks crawlers not to rely on a cached copy for more than 24 hours.
+const ROBOTS_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long an unreachable robots.txt (5xx or network error) is treated as
+/// "disallow all" before we try to fetch it again.
+const ROBOTS_RETRY_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Outcome of checking a URL against its host's robots.txt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobotsVerdict {
+    Allowed,
+    Disallowed,
+    /// robots.txt could not be retrieved; the URL must not be fetched now but
+    /// may be retried once the host's robots.txt is reachable again.
+    Unreachable,
+}
+
+struct CachedRobots {
+    robots: Robots,
+    reachable: bool,
+    expires_at: Instant,
+}
+
+/// Fetches, caches and applies robots.txt files.
+///
+/// robots.txt files the network policy forbids requesting are not fetched and
+/// count as unavailable; the fetcher refuses the pages of such hosts anyway.
+///
+/// The cache is locked only to look up a host's entry, never during a fetch.
+/// Checks of a host whose robots.txt is being fetched wait for that fetch
+/// instead of starting their own.
 pub struct RobotsChecker {
     client: Client,
-    cache: Arc<Mutex<HashMap<String, Robots>>>,
+    network: Arc<NetworkPolicy>,
+    cache: Arc<Mutex<HashMap<String, Arc<OnceCell<CachedRobots>>>>>,
 }
 
 impl RobotsChecker {
-    pub fn new(client: Client) -> Self {
+    pub fn new(client: Client, network: Arc<NetworkPolicy>) -> Self {
         RobotsChecker {
             client,
+            network,
             cache: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    pub async fn is_allowed(&self, url: &str, user_agent: &str) -> bool {
+    /// Checks `url` against its host's robots.txt and returns the verdict
+    /// together with the `Crawl-delay` the host asks for, so the delay is
+    /// picked up again whenever robots.txt is re-fetched.
+    pub async fn check(&self, url: &str, user_agent: &str) -> (RobotsVerdict, Option<Duration>) {
         let parsed_url = match Url::parse(url) {
             Ok(url) => url,
             Err(e) => {
                 warn!("Failed to parse URL {}: {}", url, e);
-                return false
+                return (RobotsVerdict::Disallowed, None)
             },
         };
 
-        let robots_url = match (parsed_url.scheme(), parsed_url.host_str()) {
-            (scheme, Some(host)) => format!("{}://{}/robots.txt", scheme, host),
-            _ => {
+        let (robots, reachable) = match self.robots_for(&parsed_url, user_agent).await {
+            Some(entry) => entry,
+            None => {
                 warn!("Could not construct robots.txt URL for {}", url);
-                return true
+                return (RobotsVerdict::Allowed, None)
             },
         };
 
+        if !reachable {
+            info!("robots.txt for {} is unreachable, deferring", url);
+            return (RobotsVerdict::Unreachable, None);
+        }
+
+        let allowed = robots.is_relative_allowed(parsed_url.path());
+        info!("URL {} is {} by robots.txt", url, if allowed { "allowed" } else { "disallowed" });
+        let verdict = if allowed { RobotsVerdict::Allowed } else { RobotsVerdict::Disallowed };
+        (verdict, robots.crawl_delay())
+    }
+
+    /// Sitemaps listed in the robots.txt of the host of `url`.
+    pub async fn sitemaps(&self, url: &str, user_agent: &str) -> Vec<String> {
+        let Ok(parsed_url) = Url::parse(url) else {
+            return Vec::new();
+        };
+        match self.robots_for(&parsed_url, user_agent).await {
+            Some((robots, _)) => robots.sitemaps().iter().map(|u| u.to_string()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    async fn robots_for(&self, parsed_url: &Url, user_agent: &str) -> Option<(Robots, bool)> {
+        // robots.txt applies to one origin: scheme, host and port
+        let origin = parsed_url.origin();
+        if !origin.is_tuple() {
+            return None;
+        }
+        let robots_url = format!("{}/robots.txt", origin.ascii_serialization());
+
         info!("Checking robots.txt at: {}", robots_url);
-        let robots = {
-            let mut cache = self.cache.lock().await;
-            if !cache.contains_key(&robots_url) {
-                info!("Fetching robots.txt from {}", robots_url);
-                let robots_content = match self.client.get(&robots_url).send().await {
-                    Ok(response) => {
-                        if response.status().is_success() {
+        let entry = {
+            let mut cache = self.cache.lock().unwrap();
+            let now = Instant::now();
+            match cache.get(&robots_url).filter(|entry| entry.get().is_none_or(|cached| cached.expires_at > now)) {
+                Some(entry) => entry.clone(),
+                None => {
+                    // Drop every expired entry, not just this one, so the cache stays
+                    // bounded by the number of hosts seen within the TTL.
+                    cache.retain(|_, entry| entry.get().is_none_or(|cached| cached.expires_at > now));
+                    let entry = Arc::new(OnceCell::new());
+                    cache.insert(robots_url.clone(), entry.clone());
+                    entry
+                }
+            }
+        };
+
+        let cached = entry.get_or_init(|| self.fetch(&robots_url, user_agent)).await;
+        Some((cached.robots.clone(), cached.reachable))
+    }
+
+    async fn fetch(&self, robots_url: &str, user_agent: &str) -> CachedRobots {
+        let blocked = Url::parse(robots_url).ok().and_then(|url| self.network.check_url(&url).err());
+        if let Some(e) = blocked {
+            warn!("Not fetching robots.txt from {}: {}", robots_url, e);
+            return CachedRobots {
+                robots: Robots::from_access(AccessResult::Unavailable, user_agent),
+                reachable: true,
+                expires_at: Instant::now() + ROBOTS_CACHE_TTL,
+            };
+        }
+
+        info!("Fetching robots.txt from {}", robots_url);
+        let (robots, reachable) = match self.client.get(robots_url).send().await {
+            Ok(response) => {
+                let status = response.status();
+                if status.is_success() {
+                    match response.bytes().await {
+                        Ok(body) => {
                             info!("Successfully fetched robots.txt from {}", robots_url);
-                            response.text().await.unwrap_or_default()
-                        } else {
-                            warn!("Failed to fetch robots.txt from {} (status: {})", robots_url, response.status());
-                            String::new() // Allow crawling if robots.txt returns error
+                            (Robots::from_access(AccessResult::Successful(&body), user_agent), true)
+                        }
+                        Err(e) => {
+                            warn!("Error reading robots.txt from {}: {}", robots_url, e);
+                            (Robots::from_access(AccessResult::Unreachable, user_agent), false)
                         }
-                    },
-                    Err(e) => {
-                        warn!("Error fetching robots.txt from {}: {}", robots_url, e);
-                        String::new() // Allow crawling if robots.txt can't be fetched
                     }
-                };
-                let robots_file = Robots::from_bytes(robots_content.as_bytes(), user_agent);
-                cache.insert(robots_url.clone(), robots_file);
-            } else {
-                info!("Using cached robots.txt for {}", robots_url);
+                } else if status.is_server_error() {
+                    warn!("robots.txt at {} is unreachable (status: {}), disallowing for now", robots_url, status);
+                    (Robots::from_access(AccessResult::Unreachable, user_agent), false)
+                } else {
+                    // 4xx (and anything else that is not a server error) means "unavailable"
+                    warn!("robots.txt at {} is unavailable (status: {}), allowing all", robots_url, status);
+                    (Robots::from_access(AccessResult::Unavailable, user_agent), true)
+                }
+            },
+            Err(e) if network::blocked_reason(&e).is_some() => {
+                warn!("Not fetching robots.txt from {}: {}", robots_url, e);
+                (Robots::from_access(AccessResult::Unavailable, user_agent), true)
+            }
+            Err(e) => {
+                warn!("Error fetching robots.txt from {}: {}, disallowing for now", robots_url, e);
+                (Robots::from_access(AccessResult::Unreachable, user_agent), false)
             }
-            cache.get(&robots_url).unwrap().clone()
         };
 
-        let allowed = robots.is_relative_allowed(parsed_url.path());
-        info!("URL {} is {} by robots.txt", url, if allowed { "allowed" } else { "disallowed" });
-        allowed
+        let ttl = if reachable { ROBOTS_CACHE_TTL } else { ROBOTS_RETRY_TTL };
+        CachedRobots { robots, reachable, expires_at: Instant::now() + ttl }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,300 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Upper bound for a robots.txt `Crawl-delay`, so a single host cannot stall
+/// its share of the crawl for minutes at a time.
+const MAX_CRAWL_DELAY: Duration = Duration::from_secs(60);
+
+/// Weight of the newest response in a host's latency and error averages.
+const HEALTH_SMOOTHING: f64 = 0.2;
+
+/// A host is asked at most once per this many of its average response times.
+const LATENCY_INTERVAL_FACTOR: f64 = 2.0;
+
+/// A host failing every request is asked this many times less often, on top
+/// of its interval.
+const ERROR_SLOWDOWN: f64 = 8.0;
+
+/// Upper bound for the interval of a slow or failing host.
+const MAX_ADAPTIVE_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Error rate below which a host counts as healthy again.
+const RECOVERED_ERROR_RATE: f64 = 0.05;
+
+/// Healthy hosts not asked for this long are forgotten. Their crawl delay is
+/// recorded again with the next robots.txt check.
+const IDLE_HOST_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+/// Slow or failing hosts are remembered longer, so they are not asked at full
+/// speed again right away, but not forever.
+const DEGRADED_HOST_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// How often forgotten hosts are looked for.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Moving averages of a host's recent responses.
+#[derive(Debug, Clone, Copy, Default)]
+struct HostHealth {
+    latency_secs: f64,
+    error_rate: f64,
+}
+
+struct HostState {
+    /// Interval from the robots.txt `Crawl-delay`, if recorded
+    interval: Option<Duration>,
+    next_allowed: Option<Instant>,
+    health: Option<HostHealth>,
+    last_used: Instant,
+}
+
+impl HostState {
+    fn new(now: Instant) -> Self {
+        HostState { interval: None, next_allowed: None, health: None, last_used: now }
+    }
+}
+
+struct HostTiming {
+    hosts: HashMap<String, HostState>,
+    last_pruned: Instant,
+}
+
+/// Request timing per host, shared by the schedulers of all running jobs so
+/// that parallel jobs together still respect each host's request interval.
+///
+/// A host's interval is the configured `request_delay` or its robots.txt
+/// `Crawl-delay`, whichever is larger. It stretches automatically while the
+/// host answers slowly or with temporary errors, and a `Retry-After` pauses
+/// the host altogether. Hosts that have not been asked for a while are
+/// forgotten, so the timing of a long-running crawler stays bounded.
+pub struct HostPoliteness {
+    min_interval: Duration,
+    timing: Mutex<HostTiming>,
+}
+
+impl HostPoliteness {
+    pub fn new(min_interval: Duration) -> Self {
+        HostPoliteness {
+            min_interval,
+            timing: Mutex::new(HostTiming { hosts: HashMap::new(), last_pruned: Instant::now() }),
+        }
+    }
+
+    /// Records the robots.txt `Crawl-delay` for `host` and returns whether
+    /// it changed the host's interval.
+    pub fn set_crawl_delay(&self, host: &str, crawl_delay: Option<Duration>) -> bool {
+        let interval = crawl_delay
+            .map(|d| d.min(MAX_CRAWL_DELAY).max(self.min_interval))
+            .unwrap_or(self.min_interval);
+        let now = Instant::now();
+        let mut timing = self.timing.lock().unwrap();
+        let state = timing.hosts.entry(host.to_string()).or_insert_with(|| HostState::new(now));
+        state.last_used = now;
+        state.interval.replace(interval) != Some(interval)
+    }
+
+    /// Records how long a request to `host` took and whether it failed with
+    /// a temporary error, and returns the host's resulting interval.
+    pub fn record_response(&self, host: &str, latency: Duration, failed: bool) -> Duration {
+        let now = Instant::now();
+        let mut timing = self.timing.lock().unwrap();
+        let sample = HostHealth {
+            latency_secs: latency.as_secs_f64(),
+            error_rate: if failed { 1.0 } else { 0.0 },
+        };
+        let state = timing.hosts.entry(host.to_string()).or_insert_with(|| HostState::new(now));
+        state.last_used = now;
+        match &mut state.health {
+            Some(health) => {
+                health.latency_secs += HEALTH_SMOOTHING * (sample.latency_secs - health.latency_secs);
+                health.error_rate += HEALTH_SMOOTHING * (sample.error_rate - health.error_rate);
+            }
+            None => state.health = Some(sample),
+        }
+        self.interval(state)
+    }
+
+    /// Holds back every request to `host` for `duration`, as asked by a
+    /// `Retry-After` header.
+    pub fn pause(&self, host: &str, duration: Duration) {
+        let now = Instant::now();
+        let until = now + duration;
+        let mut timing = self.timing.lock().unwrap();
+        let state = timing.hosts.entry(host.to_string()).or_insert_with(|| HostState::new(now));
+        state.next_allowed = Some(state.next_allowed.map_or(until, |t| t.max(until)));
+    }
+
+    fn base_interval(&self, state: &HostState) -> Duration {
+        state.interval.unwrap_or(self.min_interval)
+    }
+
+    /// The request interval of a host: its base interval, stretched by its
+    /// average latency and error rate.
+    fn interval(&self, state: &HostState) -> Duration {
+        let base = self.base_interval(state);
+        let Some(health) = state.health else {
+            return base;
+        };
+        let adaptive = base.as_secs_f64().max(health.latency_secs * LATENCY_INTERVAL_FACTOR)
+            * (1.0 + health.error_rate * ERROR_SLOWDOWN);
+        Duration::from_secs_f64(adaptive.min(MAX_ADAPTIVE_INTERVAL.as_secs_f64())).max(base)
+    }
+
+    /// Whether a host answers as fast and as reliably as its base interval
+    /// assumes.
+    fn is_healthy(&self, state: &HostState) -> bool {
+        state.health.is_none_or(|health| {
+            health.error_rate < RECOVERED_ERROR_RATE
+                && health.latency_secs * LATENCY_INTERVAL_FACTOR <= self.base_interval(state).as_secs_f64()
+        })
+    }
+
+    /// Forgets hosts whose next slot has passed and that have not been asked
+    /// for a while.
+    fn prune(&self, timing: &mut HostTiming, now: Instant) {
+        if now.duration_since(timing.last_pruned) < PRUNE_INTERVAL {
+            return;
+        }
+        timing.last_pruned = now;
+        timing.hosts.retain(|_, state| {
+            let expiry = if self.is_healthy(state) { IDLE_HOST_EXPIRY } else { DEGRADED_HOST_EXPIRY };
+            state.next_allowed.is_some_and(|t| t > now) || now.duration_since(state.last_used) < expiry
+        });
+    }
+
+    /// Reserves the next request slot of `host` if its interval has elapsed.
+    fn try_acquire(&self, host: &str, now: Instant) -> bool {
+        let mut timing = self.timing.lock().unwrap();
+        self.prune(&mut timing, now);
+        let state = timing.hosts.entry(host.to_string()).or_insert_with(|| HostState::new(now));
+        if state.next_allowed.is_some_and(|t| t > now) {
+            return false;
+        }
+        state.next_allowed = Some(now + self.interval(state));
+        state.last_used = now;
+        true
+    }
+
+    /// Waits until the interval of `host` has elapsed and reserves its next
+    /// request slot, for requests made outside a [`HostScheduler`].
+    pub async fn acquire(&self, host: &str) {
+        loop {
+            let now = Instant::now();
+            if self.try_acquire(host, now) {
+                return;
+            }
+            tokio::time::sleep(self.ready_in(host, now)).await;
+        }
+    }
+
+    fn ready_in(&self, host: &str, now: Instant) -> Duration {
+        self.timing.lock().unwrap()
+            .hosts
+            .get(host)
+            .and_then(|state| state.next_allowed)
+            .map_or(Duration::ZERO, |t| t.saturating_duration_since(now))
+    }
+}
+
+/// Per-host politeness scheduler of a single crawl job.
+///
+/// URLs are kept in one queue per host. A host becomes ready again only after
+/// its request interval, tracked by the shared [`HostPoliteness`], has passed.
+/// Batches are drawn round-robin across the ready hosts, one URL per host per
+/// batch.
+pub struct HostScheduler {
+    politeness: Arc<HostPoliteness>,
+    queues: HashMap<String, VecDeque<(String, usize)>>,
+    rotation: VecDeque<String>,
+    len: usize,
+}
+
+impl HostScheduler {
+    pub fn new(politeness: Arc<HostPoliteness>) -> Self {
+        HostScheduler {
+            politeness,
+            queues: HashMap::new(),
+            rotation: VecDeque::new(),
+            len: 0,
+        }
+    }
+
+    /// Host key used for scheduling. URLs without a host share the empty key.
+    pub fn host_key(url: &str) -> String {
+        Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+            .unwrap_or_default()
+    }
+
+    pub fn push(&mut self, url: String, depth: usize) {
+        let host = Self::host_key(&url);
+        let queue = self.queues.entry(host.clone()).or_default();
+        if queue.is_empty() {
+            self.rotation.push_back(host);
+        }
+        queue.push_back((url, depth));
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Takes up to `max` URLs from hosts whose interval has elapsed, visiting
+    /// hosts in round-robin order. Each selected host is pushed back by its
+    /// interval, for every job, before the batch is returned.
+    pub fn next_batch(&mut self, max: usize) -> Vec<(String, usize)> {
+        let now = Instant::now();
+        let mut batch = Vec::new();
+
+        for _ in 0..self.rotation.len() {
+            if batch.len() >= max {
+                break;
+            }
+            let Some(host) = self.rotation.pop_front() else {
+                break;
+            };
+
+            if !self.politeness.try_acquire(&host, now) {
+                self.rotation.push_back(host);
+                continue;
+            }
+
+            let queue = self.queues.get_mut(&host).expect("rotation and queues out of sync");
+            if let Some(entry) = queue.pop_front() {
+                batch.push(entry);
+                self.len -= 1;
+            }
+
+            if queue.is_empty() {
+                self.queues.remove(&host);
+            } else {
+                self.rotation.push_back(host);
+            }
+        }
+
+        batch
+    }
+
+    /// Time until the next queued host becomes ready, or `None` if nothing is queued.
+    pub fn next_ready_in(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.rotation
+            .iter()
+            .map(|host| self.politeness.ready_in(host, now))
+            .min()
+    }
+
+    /// Removes and returns every queued URL.
+    pub fn drain(&mut self) -> Vec<(String, usize)> {
+        self.rotation.clear();
+        self.len = 0;
+        self.queues.drain().flat_map(|(_, queue)| queue).collect()
+    }
+}
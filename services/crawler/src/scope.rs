@@ -0,0 +1,75 @@
+use crate::error::CrawlerError;
+use regex::Regex;
+use url::Url;
+
+/// Which URLs a crawl job may visit.
+///
+/// Domains match the host itself and all of its subdomains. Denied domains
+/// and exclude patterns always win; when allowed domains or include patterns
+/// are given, a URL has to match at least one of them. Patterns are matched
+/// against the full URL.
+#[derive(Debug, Clone, Default)]
+pub struct CrawlScope {
+    allowed_domains: Vec<String>,
+    denied_domains: Vec<String>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl CrawlScope {
+    /// A scope that lets every URL through.
+    pub fn unrestricted() -> Self {
+        Self::default()
+    }
+
+    pub fn new(
+        allowed_domains: &[String],
+        denied_domains: &[String],
+        include_patterns: &[String],
+        exclude_patterns: &[String],
+    ) -> Result<Self, CrawlerError> {
+        Ok(CrawlScope {
+            allowed_domains: normalize_domains(allowed_domains),
+            denied_domains: normalize_domains(denied_domains),
+            include: compile_patterns(include_patterns)?,
+            exclude: compile_patterns(exclude_patterns)?,
+        })
+    }
+
+    pub fn allows(&self, url: &str) -> bool {
+        let host = match Url::parse(url).ok().and_then(|u| u.host_str().map(|h| h.to_lowercase())) {
+            Some(host) => host,
+            None => return false,
+        };
+
+        if self.denied_domains.iter().any(|d| domain_matches(&host, d)) {
+            return false;
+        }
+        if !self.allowed_domains.is_empty() && !self.allowed_domains.iter().any(|d| domain_matches(&host, d)) {
+            return false;
+        }
+        if self.exclude.iter().any(|re| re.is_match(url)) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(|re| re.is_match(url))
+    }
+}
+
+fn normalize_domains(domains: &[String]) -> Vec<String> {
+    domains
+        .iter()
+        .map(|d| d.trim().trim_start_matches('.').to_lowercase())
+        .filter(|d| !d.is_empty())
+        .collect()
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Regex>, CrawlerError> {
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).map_err(|e| CrawlerError::InvalidScope(format!("invalid pattern '{}': {}", p, e))))
+        .collect()
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.'))
+}
@@ -0,0 +1,53 @@
+/// Number of consecutive words that make up one feature of the fingerprint.
+const SHINGLE_SIZE: usize = 3;
+
+/// Computes the 64-bit SimHash of a text, over overlapping word shingles.
+///
+/// Texts that differ in a few words (a timestamp, a session token, a changed
+/// counter) get fingerprints that differ in only a few bits, while unrelated
+/// texts differ in about half of them. Returns `None` for texts without words.
+pub fn simhash(text: &str) -> Option<u64> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+
+    let mut weights = [0i64; 64];
+    for shingle in words.windows(SHINGLE_SIZE.min(words.len())) {
+        let hash = feature_hash(shingle);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+
+    Some(weights.iter().enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0u64, |fingerprint, (bit, _)| fingerprint | (1 << bit)))
+}
+
+/// A stable 64-bit hash of a shingle: FNV-1a, followed by the SplitMix64
+/// finalizer so that every output bit depends on every input byte. The
+/// fingerprints are stored, so the hash must not change between builds.
+fn feature_hash(shingle: &[String]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for word in shingle {
+        for byte in word.bytes().chain(std::iter::once(b' ')) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
@@ -0,0 +1,270 @@
+use crate::error::CrawlerError;
+use crate::network::NetworkPolicy;
+use crate::scheduler::{HostPoliteness, HostScheduler};
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use log::{debug, info, warn};
+use quick_xml::events::Event;
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::NsReader;
+use reqwest::Client;
+use std::collections::{HashSet, VecDeque};
+use std::io::Read;
+use url::Url;
+
+/// Largest sitemap we read, after decompression. The sitemap protocol caps a
+/// single file at 50MB uncompressed.
+const MAX_SITEMAP_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Namespace of the elements defined by the sitemap protocol.
+const SITEMAP_NAMESPACE: &[u8] = b"http://www.sitemaps.org/schemas/sitemap/0.9";
+
+/// How many sitemap files (indexes included) are read per site.
+const MAX_SITEMAP_FILES: usize = 20;
+
+/// How many URLs are taken from the sitemaps of a single site.
+const MAX_SITEMAP_URLS: usize = 5_000;
+
+/// A `<url>` entry of a sitemap.
+#[derive(Debug, Clone)]
+pub struct SitemapEntry {
+    pub loc: String,
+    pub lastmod: Option<DateTime<Utc>>,
+    pub changefreq: Option<String>,
+    /// Priority relative to the site's other pages, 0.0 to 1.0
+    pub priority: Option<f32>,
+}
+
+impl SitemapEntry {
+    /// Frontier priority boost (0 to 13) derived from the entry's `<priority>`
+    /// and `<changefreq>`. Entries without either get the protocol's default
+    /// priority of 0.5.
+    pub fn priority_hint(&self) -> i32 {
+        let priority = (self.priority.unwrap_or(0.5).clamp(0.0, 1.0) * 10.0).round() as i32;
+        let frequency = match self.changefreq.as_deref() {
+            Some("always") | Some("hourly") => 3,
+            Some("daily") => 2,
+            Some("weekly") => 1,
+            _ => 0,
+        };
+        priority + frequency
+    }
+}
+
+#[derive(Debug)]
+pub enum Sitemap {
+    /// A `<urlset>` (or plain text sitemap) listing pages
+    Urls(Vec<SitemapEntry>),
+    /// A `<sitemapindex>` listing further sitemaps
+    Index(Vec<String>),
+}
+
+/// Parses an XML sitemap, sitemap index or plain text sitemap. Gzipped
+/// content is detected by its magic bytes and decompressed first.
+pub fn parse_sitemap(body: &[u8]) -> Result<Sitemap, CrawlerError> {
+    let body = decompress(body)?;
+    let first = body.iter().find(|b| !b.is_ascii_whitespace()).copied();
+    if first != Some(b'<') && first != Some(0xEF) {
+        return Ok(Sitemap::Urls(parse_text_sitemap(&body)));
+    }
+
+    let mut reader = NsReader::from_reader(body.as_slice());
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut is_index = false;
+    let mut locs = Vec::new();
+    let mut entries = Vec::new();
+    // Entries are read from `<url>` / `<sitemap>` elements at `entry_depth`,
+    // their fields only from direct children. Extensions (image, video, news,
+    // ...) nest their own `<loc>` elements deeper, in their own namespaces.
+    let mut depth = 0;
+    let mut entry_depth = 0;
+    let mut current: Option<SitemapEntry> = None;
+    let mut field: Option<String> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                depth += 1;
+                let (namespace, local_name) = reader.resolve_element(e.name());
+                let name = String::from_utf8_lossy(local_name.as_ref()).to_lowercase();
+                field = None;
+                if !is_sitemap_namespace(&namespace) {
+                    continue;
+                }
+                match name.as_str() {
+                    "sitemapindex" if depth == 1 => is_index = true,
+                    "url" | "sitemap" if current.is_none() => {
+                        current = Some(SitemapEntry { loc: String::new(), lastmod: None, changefreq: None, priority: None });
+                        entry_depth = depth;
+                    }
+                    _ if current.is_some() && depth == entry_depth + 1 => field = Some(name),
+                    _ => {}
+                }
+            }
+            Ok(Event::Text(text)) => {
+                if let (Some(entry), Some(name)) = (current.as_mut(), field.as_deref()) {
+                    let value = text.unescape().map_err(|e| CrawlerError::SitemapError(e.to_string()))?;
+                    set_field(entry, name, value.trim());
+                }
+            }
+            Ok(Event::CData(data)) => {
+                if let (Some(entry), Some(name)) = (current.as_mut(), field.as_deref()) {
+                    set_field(entry, name, String::from_utf8_lossy(&data).trim());
+                }
+            }
+            Ok(Event::End(e)) => {
+                if current.is_some() && depth == entry_depth {
+                    let is_sitemap = e.local_name().as_ref().eq_ignore_ascii_case(b"sitemap");
+                    if let Some(entry) = current.take().filter(|entry| !entry.loc.is_empty()) {
+                        if is_sitemap {
+                            locs.push(entry.loc);
+                        } else {
+                            entries.push(entry);
+                        }
+                    }
+                }
+                field = None;
+                depth -= 1;
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(e) => return Err(CrawlerError::SitemapError(e.to_string())),
+        }
+        buf.clear();
+    }
+
+    if is_index {
+        Ok(Sitemap::Index(locs))
+    } else {
+        Ok(Sitemap::Urls(entries))
+    }
+}
+
+/// Whether an element belongs to the sitemap protocol: it is in the
+/// sitemaps.org namespace, or in none at all as in sloppy sitemaps.
+fn is_sitemap_namespace(namespace: &ResolveResult) -> bool {
+    match namespace {
+        ResolveResult::Bound(Namespace(namespace)) => *namespace == SITEMAP_NAMESPACE,
+        ResolveResult::Unbound => true,
+        ResolveResult::Unknown(_) => false,
+    }
+}
+
+fn set_field(entry: &mut SitemapEntry, name: &str, value: &str) {
+    match name {
+        "loc" => entry.loc = value.to_string(),
+        "lastmod" => entry.lastmod = parse_w3c_datetime(value),
+        "changefreq" => entry.changefreq = Some(value.to_lowercase()),
+        "priority" => entry.priority = value.parse().ok(),
+        _ => {}
+    }
+}
+
+fn parse_text_sitemap(body: &[u8]) -> Vec<SitemapEntry> {
+    String::from_utf8_lossy(body)
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("http://") || line.starts_with("https://"))
+        .map(|line| SitemapEntry { loc: line.to_string(), lastmod: None, changefreq: None, priority: None })
+        .collect()
+}
+
+/// Parses the W3C datetime subset used by `<lastmod>`: a full RFC 3339
+/// timestamp, a timestamp without seconds, or just a date.
+fn parse_w3c_datetime(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    if let Ok(dt) = DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M%:z") {
+        return Some(dt.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+}
+
+fn decompress(body: &[u8]) -> Result<Vec<u8>, CrawlerError> {
+    if !body.starts_with(&[0x1f, 0x8b]) {
+        return Ok(body.to_vec());
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(body)
+        .take(MAX_SITEMAP_BYTES + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() as u64 > MAX_SITEMAP_BYTES {
+        return Err(CrawlerError::ContentTooLarge(decompressed.len() as u64));
+    }
+    Ok(decompressed)
+}
+
+pub async fn fetch_sitemap(client: &Client, network: &NetworkPolicy, url: &str, user_agent: &str) -> Result<Sitemap, CrawlerError> {
+    let parsed = Url::parse(url).map_err(|e| CrawlerError::UrlNormalizationError(e.to_string()))?;
+    network.check_url(&parsed)?;
+    let mut response = client
+        .get(url)
+        .header(reqwest::header::USER_AGENT, user_agent)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(CrawlerError::StatusError(response.status()));
+    }
+    if let Some(length) = response.content_length().filter(|length| *length > MAX_SITEMAP_BYTES) {
+        return Err(CrawlerError::ContentTooLarge(length));
+    }
+
+    // The body is read chunk by chunk, as Content-Length may be missing or wrong
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(CrawlerError::ResponseError)? {
+        body.extend_from_slice(&chunk);
+        if body.len() as u64 > MAX_SITEMAP_BYTES {
+            return Err(CrawlerError::ContentTooLarge(body.len() as u64));
+        }
+    }
+    parse_sitemap(&body)
+}
+
+/// Reads the given sitemaps, following sitemap indexes, and returns the page
+/// entries found. Unreadable sitemaps are skipped. The number of files read
+/// and entries returned is capped. Each request waits for its host's turn in
+/// `politeness`, like page fetches do.
+pub async fn discover(
+    client: &Client,
+    network: &NetworkPolicy,
+    politeness: &HostPoliteness,
+    sitemap_urls: Vec<String>,
+    user_agent: &str,
+) -> Vec<SitemapEntry> {
+    let mut queue: VecDeque<String> = sitemap_urls.into_iter().collect();
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+
+    while let Some(url) = queue.pop_front() {
+        if seen.len() >= MAX_SITEMAP_FILES || entries.len() >= MAX_SITEMAP_URLS {
+            break;
+        }
+        if !seen.insert(url.clone()) {
+            continue;
+        }
+
+        politeness.acquire(&HostScheduler::host_key(&url)).await;
+        match fetch_sitemap(client, network, &url, user_agent).await {
+            Ok(Sitemap::Index(children)) => {
+                debug!("Sitemap index {} lists {} sitemaps", url, children.len());
+                queue.extend(children);
+            }
+            Ok(Sitemap::Urls(urls)) => {
+                info!("Sitemap {} lists {} URLs", url, urls.len());
+                let remaining = MAX_SITEMAP_URLS - entries.len();
+                entries.extend(urls.into_iter().take(remaining));
+            }
+            Err(e) => warn!("Failed to read sitemap {}: {}", url, e),
+        }
+    }
+
+    entries
+}
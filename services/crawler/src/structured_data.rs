@@ -0,0 +1,246 @@
+use lazy_static::lazy_static;
+use log::debug;
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{json, Map, Value};
+
+/// Most JSON-LD items kept per page.
+const MAX_JSON_LD_ITEMS: usize = 20;
+
+/// Most top-level microdata items kept per page.
+const MAX_MICRODATA_ITEMS: usize = 20;
+
+/// How deep nested microdata items are followed.
+const MAX_MICRODATA_DEPTH: usize = 5;
+
+lazy_static! {
+    static ref META_SELECTOR: Selector = Selector::parse("meta[content]").unwrap();
+    static ref SCRIPT_SELECTOR: Selector = Selector::parse("script[type]").unwrap();
+    static ref ITEM_SELECTOR: Selector = Selector::parse("[itemscope]:not([itemprop])").unwrap();
+}
+
+/// Extracts the structured data of a page: JSON-LD blocks, OpenGraph,
+/// `article:*` and Twitter card properties, and schema.org microdata.
+///
+/// Besides the raw sets (`json_ld`, `opengraph`, `article`, `twitter`,
+/// `microdata`), the most useful facts are picked from whichever source has
+/// them into flat `site_name`, `author`, `published_time` and `modified_time`
+/// fields. Dates are kept verbatim.
+pub fn extract(document: &Html) -> Map<String, Value> {
+    let mut opengraph = Map::new();
+    let mut article = Map::new();
+    let mut twitter = Map::new();
+    let mut author_meta = None;
+
+    for element in document.select(&META_SELECTOR) {
+        let Some(content) = element.value().attr("content").map(str::trim).filter(|c| !c.is_empty()) else {
+            continue;
+        };
+        // OpenGraph uses `property`, Twitter cards `name`, but both are mixed up in the wild
+        let Some(key) = element.value().attr("property").or_else(|| element.value().attr("name")) else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+
+        if let Some(field) = key.strip_prefix("og:") {
+            insert_repeated(&mut opengraph, field, content);
+        } else if let Some(field) = key.strip_prefix("article:") {
+            insert_repeated(&mut article, field, content);
+        } else if let Some(field) = key.strip_prefix("twitter:") {
+            insert_repeated(&mut twitter, field, content);
+        } else if key == "author" && author_meta.is_none() {
+            author_meta = Some(content.to_string());
+        }
+    }
+
+    let json_ld = extract_json_ld(document);
+    let microdata = extract_microdata(document);
+
+    let site_name = first_string(&opengraph, "site_name")
+        .or_else(|| json_ld_site_name(&json_ld));
+    let author = author_meta
+        .or_else(|| first_string(&article, "author"))
+        .or_else(|| json_ld_field(&json_ld, "author"))
+        .or_else(|| microdata_field(&microdata, "author"));
+    let published_time = first_string(&article, "published_time")
+        .or_else(|| json_ld_field(&json_ld, "datePublished"))
+        .or_else(|| microdata_field(&microdata, "datePublished"));
+    let modified_time = first_string(&article, "modified_time")
+        .or_else(|| first_string(&opengraph, "updated_time"))
+        .or_else(|| json_ld_field(&json_ld, "dateModified"))
+        .or_else(|| microdata_field(&microdata, "dateModified"));
+
+    let mut data = Map::new();
+    for (key, value) in [
+        ("site_name", site_name),
+        ("author", author),
+        ("published_time", published_time),
+        ("modified_time", modified_time),
+    ] {
+        if let Some(value) = value {
+            data.insert(key.to_string(), json!(value));
+        }
+    }
+    for (key, map) in [("opengraph", opengraph), ("article", article), ("twitter", twitter)] {
+        if !map.is_empty() {
+            data.insert(key.to_string(), Value::Object(map));
+        }
+    }
+    if !json_ld.is_empty() {
+        data.insert("json_ld".to_string(), Value::Array(json_ld));
+    }
+    if !microdata.is_empty() {
+        data.insert("microdata".to_string(), Value::Array(microdata));
+    }
+    data
+}
+
+/// Adds a property, turning it into an array when it repeats (several
+/// `og:image` tags, for example).
+fn insert_repeated(map: &mut Map<String, Value>, key: &str, value: &str) {
+    match map.get_mut(key) {
+        Some(Value::Array(values)) => values.push(json!(value)),
+        Some(existing) => *existing = json!([existing.take(), value]),
+        None => {
+            map.insert(key.to_string(), json!(value));
+        }
+    }
+}
+
+fn first_string(map: &Map<String, Value>, key: &str) -> Option<String> {
+    value_to_string(map.get(key)?)
+}
+
+/// A property value as text: strings as they are, the `name` of objects
+/// (e.g. a `Person`), the first usable entry of arrays.
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Object(object) => object.get("name")
+            .or_else(|| object.get("properties").and_then(|p| p.get("name")))
+            .and_then(value_to_string),
+        Value::Array(values) => values.iter().find_map(value_to_string),
+        _ => None,
+    }
+}
+
+fn extract_json_ld(document: &Html) -> Vec<Value> {
+    let mut items = Vec::new();
+    let blocks = document.select(&SCRIPT_SELECTOR)
+        .filter(|script| script.value().attr("type").is_some_and(|t| t.trim().eq_ignore_ascii_case("application/ld+json")));
+
+    for script in blocks {
+        let text = script.text().collect::<String>();
+        match serde_json::from_str::<Value>(text.trim()) {
+            Ok(value) => flatten_json_ld(value, &mut items),
+            Err(e) => debug!("Ignoring invalid JSON-LD block: {}", e),
+        }
+    }
+
+    items.truncate(MAX_JSON_LD_ITEMS);
+    items
+}
+
+/// Splits top-level arrays and `@graph` containers into separate items.
+fn flatten_json_ld(value: Value, items: &mut Vec<Value>) {
+    match value {
+        Value::Array(values) => values.into_iter().for_each(|v| flatten_json_ld(v, items)),
+        Value::Object(mut object) => match object.remove("@graph") {
+            Some(graph) => flatten_json_ld(graph, items),
+            None => items.push(Value::Object(object)),
+        },
+        _ => {}
+    }
+}
+
+fn json_ld_field(items: &[Value], field: &str) -> Option<String> {
+    items.iter().find_map(|item| item.get(field).and_then(value_to_string))
+}
+
+fn json_ld_site_name(items: &[Value]) -> Option<String> {
+    let is_website = |item: &&Value| match item.get("@type") {
+        Some(Value::String(t)) => t == "WebSite",
+        Some(Value::Array(types)) => types.iter().any(|t| t == "WebSite"),
+        _ => false,
+    };
+    items.iter()
+        .find(is_website)
+        .and_then(|site| site.get("name").and_then(value_to_string))
+        .or_else(|| json_ld_field(items, "publisher"))
+}
+
+fn extract_microdata(document: &Html) -> Vec<Value> {
+    document.select(&ITEM_SELECTOR)
+        // Items nested in another item's property are part of that item
+        .filter(|item| !item.ancestors().filter_map(ElementRef::wrap).any(|a| a.value().attr("itemscope").is_some()))
+        .take(MAX_MICRODATA_ITEMS)
+        .map(|item| microdata_item(item, 0))
+        .collect()
+}
+
+fn microdata_item(item: ElementRef, depth: usize) -> Value {
+    let mut properties = Map::new();
+    if depth < MAX_MICRODATA_DEPTH {
+        collect_microdata_properties(item, depth, &mut properties);
+    }
+
+    let mut value = json!({ "properties": properties });
+    if let Some(item_type) = item.value().attr("itemtype") {
+        value["type"] = json!(item_type.trim());
+    }
+    value
+}
+
+fn collect_microdata_properties(element: ElementRef, depth: usize, properties: &mut Map<String, Value>) {
+    // Walked with an explicit stack, in document order, so deeply nested
+    // markup cannot exhaust the call stack
+    let mut stack: Vec<ElementRef> = element.children().filter_map(ElementRef::wrap).collect();
+    stack.reverse();
+    while let Some(child) = stack.pop() {
+        let is_scope = child.value().attr("itemscope").is_some();
+        if let Some(names) = child.value().attr("itemprop") {
+            let value = if is_scope {
+                microdata_item(child, depth + 1)
+            } else {
+                json!(microdata_value(child))
+            };
+            for name in names.split_whitespace() {
+                match properties.get_mut(name) {
+                    Some(Value::Array(values)) => values.push(value.clone()),
+                    Some(existing) => *existing = json!([existing.take(), value.clone()]),
+                    None => {
+                        properties.insert(name.to_string(), value.clone());
+                    }
+                }
+            }
+        }
+        // Properties inside a nested item belong to that item
+        if !is_scope {
+            let first = stack.len();
+            stack.extend(child.children().filter_map(ElementRef::wrap));
+            stack[first..].reverse();
+        }
+    }
+}
+
+/// The value of a non-item property, taken from the attribute the element
+/// type keeps it in, or its text.
+fn microdata_value(element: ElementRef) -> String {
+    let el = element.value();
+    let attribute = match el.name() {
+        "meta" => el.attr("content"),
+        "a" | "link" | "area" => el.attr("href"),
+        "img" | "audio" | "video" | "source" | "iframe" | "embed" => el.attr("src"),
+        "object" => el.attr("data"),
+        "data" | "meter" => el.attr("value"),
+        "time" => el.attr("datetime"),
+        _ => None,
+    };
+    attribute
+        .map(|value| value.trim().to_string())
+        .unwrap_or_else(|| element.text().collect::<Vec<_>>().join(" ").split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn microdata_field(items: &[Value], field: &str) -> Option<String> {
+    items.iter().find_map(|item| item.get("properties")?.get(field).and_then(value_to_string))
+}
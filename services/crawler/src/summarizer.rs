@@ -1,42 +1,160 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::cmp::Ordering;
+use lazy_static::lazy_static;
+use stopwords::{Language, Spark, Stopwords};
+use unicode_segmentation::UnicodeSegmentation;
 
-pub fn tfidf_summary(content: &str, summary_length: usize) -> String {
-    let sentences: Vec<&str> = content.split('.').collect();
-    
-    let mut word_freq = HashMap::new();
-    let mut word_sentences = HashMap::new();
-    
-    for (i, &sentence) in sentences.iter().enumerate() {
-        let sentence_words: Vec<&str> = sentence.split_whitespace().collect();
-        for word in &sentence_words {
-            *word_freq.entry(word.to_lowercase()).or_insert(0) += 1;
-            word_sentences.entry(word.to_lowercase()).or_insert(Vec::new()).push(i);
+/// Meta descriptions shorter than this are usually placeholders (the site
+/// name, "Home") and lose against a summary of the content.
+const MIN_DESCRIPTION_CHARS: usize = 50;
+
+/// Sentences with fewer content words are menu entries, captions and the
+/// like rather than prose.
+const MIN_SENTENCE_WORDS: usize = 3;
+
+/// Abbreviations after which a period does not end the sentence.
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "mt", "vs", "etc", "e.g", "i.e", "cf",
+    "approx", "no", "nos", "fig", "figs", "vol", "p", "pp", "ch", "sec", "ed", "eds", "inc",
+    "ltd", "co", "corp", "dept", "univ", "gen", "gov", "jan", "feb", "mar", "apr", "jun",
+    "jul", "aug", "sep", "sept", "oct", "nov", "dec", "u.s", "u.k", "a.m", "p.m",
+];
+
+lazy_static! {
+    static ref STOPWORDS: HashSet<&'static str> = Spark::stopwords(Language::English)
+        .map(|words| words.iter().copied().collect())
+        .unwrap_or_default();
+}
+
+/// The summary shown for a page: its meta description when it has a real
+/// one, an extractive summary of its content otherwise. Either way the
+/// result is at most `max_chars` characters long.
+pub fn page_summary(content: &str, meta_description: Option<&str>, max_chars: usize) -> String {
+    match meta_description.map(normalize_whitespace) {
+        Some(description) if description.chars().count() >= MIN_DESCRIPTION_CHARS => {
+            truncate(&description, max_chars)
         }
+        _ => summarize(content, max_chars),
+    }
+}
+
+/// Picks the most informative sentences of `content` that fit in
+/// `max_chars` characters and returns them in document order.
+///
+/// Sentences are scored by how frequent their non-stopwords are in the whole
+/// text, normalized by sentence length, with a small bonus for the opening
+/// sentences. If not even one sentence fits, the best one is cut at a word
+/// boundary.
+pub fn summarize(content: &str, max_chars: usize) -> String {
+    let sentences = sentences(content);
+    if sentences.is_empty() || max_chars == 0 {
+        return String::new();
+    }
+
+    let sentence_words: Vec<Vec<String>> = sentences.iter().map(|s| content_words(s)).collect();
+    let mut frequencies: HashMap<&str, usize> = HashMap::new();
+    for word in sentence_words.iter().flatten() {
+        *frequencies.entry(word).or_insert(0) += 1;
     }
-    
-    let total_sentences = sentences.len() as f64;
-    
-    let mut sentence_scores: Vec<(usize, f64)> = sentences.iter().enumerate()
-        .map(|(i, &sentence)| {
-            let score = sentence.split_whitespace()
-                .map(|word| {
-                    let tf = *word_freq.get(&word.to_lowercase()).unwrap_or(&0) as f64;
-                    let idf = (total_sentences / word_sentences.get(&word.to_lowercase()).map_or(1.0, |v| v.len() as f64)).ln();
-                    tf * idf
-                })
-                .sum::<f64>() / sentence.split_whitespace().count().max(1) as f64;
-            (i, score)
+    let max_frequency = frequencies.values().copied().max().unwrap_or(1) as f64;
+
+    let mut scored: Vec<(usize, f64)> = sentence_words.iter().enumerate()
+        .filter(|(_, words)| words.len() >= MIN_SENTENCE_WORDS)
+        .map(|(i, words)| {
+            let distinct: HashSet<&str> = words.iter().map(String::as_str).collect();
+            let weight: f64 = distinct.iter().map(|w| frequencies[w] as f64 / max_frequency).sum();
+            let lead_bonus = 1.0 + 0.5 / (i + 1) as f64;
+            (i, weight / (words.len() as f64).sqrt() * lead_bonus)
         })
         .collect();
-    
-    sentence_scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
-    
-    let summary_length = summary_length.min(sentences.len());
-    
-    sentence_scores.iter()
-        .take(summary_length)
-        .map(|&(i, _)| sentences[i])
-        .collect::<Vec<&str>>()
-        .join(". ")
+    // Pages made only of short fragments still get a summary
+    if scored.is_empty() {
+        scored = (0..sentences.len()).map(|i| (i, 0.0)).collect();
+    }
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal).then(a.0.cmp(&b.0)));
+
+    let mut chosen = Vec::new();
+    let mut used = 0;
+    for &(i, _) in &scored {
+        let separator = if chosen.is_empty() { 0 } else { 1 };
+        let length = sentences[i].chars().count();
+        if used + separator + length <= max_chars {
+            chosen.push(i);
+            used += separator + length;
+        }
+    }
+
+    if chosen.is_empty() {
+        return truncate(sentences[scored[0].0], max_chars);
+    }
+    chosen.sort_unstable();
+    chosen.iter().map(|&i| sentences[i]).collect::<Vec<_>>().join(" ")
+}
+
+/// Splits text into sentences following the Unicode sentence boundary rules
+/// (UAX #29), which already leave decimals, URLs and domain names intact.
+/// Boundaries after common abbreviations and initials are undone.
+pub fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = None;
+
+    for (offset, segment) in text.split_sentence_bound_indices() {
+        let start_offset = *start.get_or_insert(offset);
+        if ends_with_abbreviation(segment) {
+            continue;
+        }
+        let sentence = text[start_offset..offset + segment.len()].trim();
+        if !sentence.is_empty() {
+            sentences.push(sentence);
+        }
+        start = None;
+    }
+    if let Some(start_offset) = start {
+        let sentence = text[start_offset..].trim();
+        if !sentence.is_empty() {
+            sentences.push(sentence);
+        }
+    }
+    sentences
+}
+
+fn ends_with_abbreviation(segment: &str) -> bool {
+    let Some(last_word) = segment.trim_end().strip_suffix('.').and_then(|s| s.split_whitespace().last()) else {
+        return false;
+    };
+    let word = last_word.trim_start_matches(|c: char| !c.is_alphanumeric());
+    // A single capital letter is an initial, as in "J. R. R. Tolkien"
+    let is_initial = word.chars().count() == 1 && word.chars().all(char::is_uppercase);
+    is_initial || ABBREVIATIONS.contains(&word.to_lowercase().as_str())
+}
+
+/// The lowercased words of a sentence that carry meaning: no stopwords, no
+/// numbers.
+fn content_words(sentence: &str) -> Vec<String> {
+    sentence.unicode_words()
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(word.as_str()) && word.chars().any(char::is_alphabetic))
+        .collect()
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Cuts text to at most `max_chars` characters, at a word boundary where
+/// possible, marking the cut with an ellipsis.
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    if max_chars == 0 {
+        return String::new();
+    }
+    let cut = text.char_indices().nth(max_chars - 1).map_or(text.len(), |(i, _)| i);
+    let head = &text[..cut];
+    let head = match head.rfind(char::is_whitespace) {
+        Some(space) if space > 0 => &head[..space],
+        _ => head,
+    };
+    format!("{}…", head.trim_end_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation()))
 }
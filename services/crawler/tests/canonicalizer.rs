@@ -0,0 +1,148 @@
+use chrono::{Datelike, Utc};
+use crawler::canonicalizer::{self, UrlCanonicalizer};
+use crawler::error::CrawlerError;
+
+#[test]
+fn canonicalizes_urls() {
+    let cases: &[(&str, &str)] = &[
+        // Scheme and host are case-insensitive, path and query are not
+        ("HTTP://Example.COM/Docs/Intro", "http://example.com/Docs/Intro"),
+        ("https://example.com/search?q=Rust&Lang=EN", "https://example.com/search?Lang=EN&q=Rust"),
+        // Default ports go, others stay
+        ("http://example.com:80/a", "http://example.com/a"),
+        ("https://example.com:443/a", "https://example.com/a"),
+        ("https://example.com:8443/a", "https://example.com:8443/a"),
+        // The root keeps its slash, other paths keep theirs or its absence
+        ("https://example.com", "https://example.com/"),
+        ("https://example.com/", "https://example.com/"),
+        ("https://example.com/dir/", "https://example.com/dir/"),
+        ("https://example.com/file", "https://example.com/file"),
+        // Dot segments
+        ("https://example.com/a/./b/../c", "https://example.com/a/c"),
+        // Fragments are dropped
+        ("https://example.com/page#section-2", "https://example.com/page"),
+        ("https://example.com/page?x=1#top", "https://example.com/page?x=1"),
+        // Percent-encoding: unreserved characters decoded, hex digits uppercased
+        ("https://example.com/%7Euser/%61bc", "https://example.com/~user/abc"),
+        ("https://example.com/a%2fb?q=%3d%c3%a9", "https://example.com/a%2Fb?q=%3D%C3%A9"),
+        // Query values are left alone
+        ("https://example.com/?b=2&a=x+y", "https://example.com/?a=x+y&b=2"),
+        ("https://example.com/?a=2&a=1", "https://example.com/?a=2&a=1"),
+        // Tracking and session parameters
+        ("https://example.com/post?utm_source=feed&utm_medium=rss&id=7", "https://example.com/post?id=7"),
+        ("https://example.com/post?fbclid=abc&gclid=def", "https://example.com/post"),
+        ("https://example.com/post?UTM_Campaign=x&PHPSESSID=1&page=2", "https://example.com/post?page=2"),
+        ("https://example.com/app;jsessionid=0A1B2C?id=3", "https://example.com/app?id=3"),
+        // Short names sites also use for content are kept
+        ("https://example.com/forum?sid=12&ref=nav", "https://example.com/forum?ref=nav&sid=12"),
+        // Empty parameters and a trailing question mark
+        ("https://example.com/?&&a=1&", "https://example.com/?a=1"),
+        ("https://example.com/list?", "https://example.com/list"),
+        // Trailing dot of a fully qualified host name
+        ("https://example.com./a", "https://example.com/a"),
+    ];
+
+    let canonicalizer = UrlCanonicalizer::default();
+    for (input, expected) in cases {
+        assert_eq!(canonicalizer.canonicalize(input).unwrap(), *expected, "canonicalizing {}", input);
+    }
+}
+
+#[test]
+fn rejects_unsupported_urls() {
+    let canonicalizer = UrlCanonicalizer::default();
+    for input in ["mailto:someone@example.com", "ftp://example.com/file", "javascript:void(0)", "not a url", "/relative/path"] {
+        assert!(
+            matches!(canonicalizer.canonicalize(input), Err(CrawlerError::UrlNormalizationError(_))),
+            "accepted {}", input
+        );
+    }
+}
+
+#[test]
+fn strips_configured_parameters() {
+    let canonicalizer = UrlCanonicalizer::new(&["ref", "trk_*"]);
+    let cases: &[(&str, &str)] = &[
+        ("https://example.com/?ref=home&id=1", "https://example.com/?id=1"),
+        ("https://example.com/?trk_a=1&trk_b=2&id=1", "https://example.com/?id=1"),
+        // The defaults no longer apply
+        ("https://example.com/?utm_source=x", "https://example.com/?utm_source=x"),
+        // A prefix rule does not match the bare prefix
+        ("https://example.com/?trk=1", "https://example.com/?trk=1"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(canonicalizer.canonicalize(input).unwrap(), *expected, "canonicalizing {}", input);
+    }
+}
+
+#[test]
+fn fetches_urls_as_found() {
+    let cases: &[(&str, &str)] = &[
+        // Order, case and encoding of the query are the server's business
+        ("https://example.com/search?q=Rust&Lang=EN", "https://example.com/search?q=Rust&Lang=EN"),
+        ("https://example.com/a%2fb?utm_source=x&q=%3d", "https://example.com/a%2fb?utm_source=x&q=%3d"),
+        // Only the fragment, which is never sent, goes
+        ("https://example.com/page?x=1#top", "https://example.com/page?x=1"),
+        (" HTTP://Example.COM:80/Docs ", "http://example.com/Docs"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(canonicalizer::fetch_url(input).unwrap(), *expected, "fetch URL of {}", input);
+    }
+    assert!(canonicalizer::fetch_url("mailto:someone@example.com").is_err());
+}
+
+#[test]
+fn computes_keys_of_pages_stored_before_canonicalization() {
+    let cases: &[(&str, &str)] = &[
+        ("https://example.com/Docs/Intro/", "https://example.com/docs/intro"),
+        ("https://example.com/", "https://example.com/"),
+        ("https://example.com/search?q=Rust+Book&Lang=EN", "https://example.com/search?lang=en&q=rust+book"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(canonicalizer::legacy_url_key(input).unwrap(), *expected, "legacy key of {}", input);
+    }
+}
+
+#[test]
+fn detects_crawl_traps() {
+    let year = Utc::now().year();
+    let long_url = format!("https://example.com/{}", "a".repeat(2100));
+    let deep_url = format!("https://example.com/{}", (0..40).map(|i| format!("d{}", i)).collect::<Vec<_>>().join("/"));
+    let many_params = format!("https://example.com/shop?{}", (0..20).map(|i| format!("f{}=1", i)).collect::<Vec<_>>().join("&"));
+    let cases: Vec<(String, bool)> = vec![
+        ("https://example.com/docs/guide/intro".to_string(), false),
+        (long_url, true),
+        (deep_url, true),
+        (many_params, true),
+        // Relative links resolved against themselves
+        ("https://example.com/a/b/a/b/a/b/page".to_string(), true),
+        ("https://example.com/img/img/logo.png".to_string(), false),
+        // Numeric segments may repeat
+        ("https://example.com/2024/01/01/1".to_string(), false),
+        // Pagination
+        ("https://example.com/list?page=12".to_string(), false),
+        ("https://example.com/list?page=99999".to_string(), true),
+        ("https://example.com/blog/page/5".to_string(), false),
+        ("https://example.com/blog/page/50000".to_string(), true),
+        ("https://example.com/list?offset=200".to_string(), false),
+        ("https://example.com/list?offset=1000000".to_string(), true),
+        // WordPress post ids are not page numbers
+        ("https://example.com/?p=123456".to_string(), false),
+        // Calendars
+        (format!("https://example.com/events?year={}&month=3", year), false),
+        (format!("https://example.com/events?year={}", year + 40), true),
+        (format!("https://example.com/calendar?date={}-01-01", year + 100), true),
+        ("https://example.com/events?date=1850-06".to_string(), true),
+        (format!("https://example.com/archive/{}/05/", year - 1), false),
+        (format!("https://example.com/calendar/{}/05/", year + 50), true),
+    ];
+
+    let canonicalizer = UrlCanonicalizer::default();
+    for (input, is_trap) in &cases {
+        let result = canonicalizer.canonicalize_for_frontier(input);
+        assert_eq!(matches!(result, Err(CrawlerError::CrawlTrap(_))), *is_trap, "{}: {:?}", input, result);
+        if !is_trap {
+            assert!(result.is_ok());
+        }
+    }
+}
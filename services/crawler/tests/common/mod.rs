@@ -0,0 +1,8 @@
+use std::fs;
+use std::path::Path;
+
+/// Reads a file from `tests/fixtures`.
+pub fn load(file: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(file);
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e))
+}
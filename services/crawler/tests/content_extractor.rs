@@ -0,0 +1,161 @@
+use crawler::content_extractor::{self, Heading};
+use crawler::parser;
+use scraper::Html;
+
+mod common;
+use common::load;
+
+struct Fixture {
+    file: &'static str,
+    /// Phrases that belong to the main content
+    kept: &'static [&'static str],
+    /// Phrases from navigation, sidebars, comments and other boilerplate
+    dropped: &'static [&'static str],
+    /// The expected outline, as (level, text)
+    headings: &'static [(u8, &'static str)],
+}
+
+const FIXTURES: &[Fixture] = &[
+    Fixture {
+        file: "blog_article.html",
+        kept: &[
+            "For three years our remote build cache lived on a pair of beefy machines",
+            "Every cache entry was written to exactly one machine",
+            "The cache stopped being a pet.",
+            "we should have done it from day one.",
+        ],
+        dropped: &[
+            "Skip to content",
+            "Archive",
+            "Share this:",
+            "Previous post",
+            "Did you consider a content-addressed store",
+            "Your email address will not be published",
+            "Recent Posts",
+            "written by the developer productivity team",
+            "Proudly powered by",
+            "We use cookies",
+            "window.dataLayer",
+        ],
+        headings: &[
+            (1, "Why We Moved Our Build Cache to Object Storage"),
+            (2, "The problem with local disks"),
+            (2, "What object storage gave us"),
+            (3, "Latency numbers"),
+            (2, "What we would do differently"),
+        ],
+    },
+    Fixture {
+        file: "news_article.html",
+        kept: &[
+            "voted unanimously to add six overnight bus routes",
+            "Nurses, cleaners and cooks keep this city running at night",
+            "The expansion is expected to cost 4.2 million a year.",
+            "publish detailed timetables in August",
+        ],
+        dropped: &[
+            "Subscribe for $1",
+            "Politics",
+            "Advertisement",
+            "Bus fares frozen for another year",
+            "Get the morning briefing",
+            "Storm warning issued",
+            "All rights reserved",
+            "ad loader",
+        ],
+        headings: &[
+            (1, "City council approves expansion of night bus network"),
+            (2, "When the routes start"),
+        ],
+    },
+    Fixture {
+        file: "docs_page.html",
+        kept: &[
+            "returns a new array formed by applying a given callback function",
+            "A function to execute for each element in the array.",
+            "Empty slots in sparse arrays are skipped",
+        ],
+        dropped: &[
+            "Array.prototype.reduce()",
+            "Guides",
+            "View this page on GitHub",
+            "licensed under CC-BY-SA",
+        ],
+        headings: &[
+            (1, "Array.prototype.flatMap()"),
+            (2, "Syntax"),
+            (3, "Parameters"),
+            (3, "Return value"),
+            (2, "Description"),
+            (2, "Browser compatibility"),
+        ],
+    },
+    Fixture {
+        file: "table_layout.html",
+        kept: &[
+            "the reels would not turn",
+            "Isopropyl alcohol and cotton swabs work",
+            "Replacing the pinch roller",
+            "within the manufacturer's original specifications",
+        ],
+        dropped: &["Amplifiers", "Guestbook", "Email me", "Vintage Audio Workbench"],
+        headings: &[(1, "Restoring a 1970s Reel-to-Reel Tape Deck")],
+    },
+    Fixture {
+        file: "link_directory.html",
+        kept: &[],
+        dropped: &["Parts One", "Circuits Forum", "Ohm's law calculator", "Submit a link"],
+        headings: &[],
+    },
+];
+
+#[test]
+fn extracts_main_content_from_fixtures() {
+    for fixture in FIXTURES {
+        let content = content_extractor::extract(&Html::parse_document(&load(fixture.file)));
+
+        for phrase in fixture.kept {
+            assert!(content.text.contains(phrase), "{}: missing {:?} in\n{}", fixture.file, phrase, content.text);
+        }
+        for phrase in fixture.dropped {
+            assert!(!content.text.contains(phrase), "{}: kept boilerplate {:?} in\n{}", fixture.file, phrase, content.text);
+        }
+
+        let expected: Vec<Heading> = fixture.headings.iter()
+            .map(|&(level, text)| Heading { level, text: text.to_string() })
+            .collect();
+        assert_eq!(content.headings, expected, "{}: outline", fixture.file);
+    }
+}
+
+#[test]
+fn keeps_blocks_on_separate_lines() {
+    let content = content_extractor::extract(&Html::parse_document(&load("blog_article.html")));
+    assert!(content.text.lines().any(|line| line == "The problem with local disks"));
+}
+
+#[test]
+fn parsed_pages_carry_content_and_outline() {
+    let page = parser::parse_webpage(&load("blog_article.html"), "https://buildsmith.example/2024/03/12/build-cache/", 200).unwrap();
+    assert!(page.content.is_some_and(|content| content.contains("hashing the action key")));
+    let outline = &page.metadata.unwrap()["headings"];
+    assert_eq!(outline[0]["level"], 1);
+    assert_eq!(outline[0]["text"], "Why We Moved Our Build Cache to Object Storage");
+
+    // A page of links has no main text worth storing
+    let page = parser::parse_webpage(&load("link_directory.html"), "https://directory.example/resources", 200).unwrap();
+    assert!(page.content.is_none());
+}
+
+#[test]
+fn survives_deeply_nested_markup() {
+    let depth = 5_000;
+    let html = format!(
+        "<html><body><article><p>{}</p>{}{}</article></body></html>",
+        "Text outside the nesting stays part of the content of this page. ".repeat(4),
+        "<div><span>".repeat(depth),
+        "</span></div>".repeat(depth),
+    );
+    let content = content_extractor::extract(&Html::parse_document(&html));
+    assert!(content.text.contains("Text outside the nesting"));
+}
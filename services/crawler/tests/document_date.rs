@@ -0,0 +1,87 @@
+use chrono::{DateTime, Duration, Utc};
+use crawler::document_date::{from_last_modified, from_pdf_info, parse_date, DateSource};
+
+fn utc(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+}
+
+#[test]
+fn parses_metadata_dates() {
+    let cases: &[(&str, Option<&str>)] = &[
+        ("2024-03-12T08:30:15Z", Some("2024-03-12T08:30:15Z")),
+        ("2024-03-12T08:30:15.250+02:00", Some("2024-03-12T06:30:15.250Z")),
+        ("2024-03-12T08:30+02:00", Some("2024-03-12T06:30:00Z")),
+        ("2024-03-12T08:30:15+0200", Some("2024-03-12T06:30:15Z")),
+        ("2024-03-12 08:30:15+02:00", Some("2024-03-12T06:30:15Z")),
+        ("Tue, 12 Mar 2024 08:30:15 GMT", Some("2024-03-12T08:30:15Z")),
+        // Times without a zone are UTC
+        ("2024-03-12T08:30:15", Some("2024-03-12T08:30:15Z")),
+        ("2024-03-12T08:30", Some("2024-03-12T08:30:00Z")),
+        ("2024-03-12 08:30:15", Some("2024-03-12T08:30:15Z")),
+        (" 2024-03-12 ", Some("2024-03-12T00:00:00Z")),
+        ("2024-03-12, updated later", Some("2024-03-12T00:00:00Z")),
+        // Malformed
+        ("", None),
+        ("yesterday", None),
+        ("12/03/2024", None),
+        ("2024-13-01", None),
+        ("2024-02-30T10:00:00Z", None),
+        ("2024-03", None),
+        ("März 2024", None),
+    ];
+    for (value, expected) in cases {
+        assert_eq!(parse_date(value), expected.map(utc), "{:?}", value);
+    }
+}
+
+#[test]
+fn parses_pdf_dates() {
+    let cases: &[(&str, Option<&str>)] = &[
+        ("D:20230415120000Z", Some("2023-04-15T12:00:00Z")),
+        ("D:20230415120000+02'00'", Some("2023-04-15T10:00:00Z")),
+        ("D:20230415120000-05'30'", Some("2023-04-15T17:30:00Z")),
+        ("D:20230415120000+02'00", Some("2023-04-15T10:00:00Z")),
+        ("20230415120000", Some("2023-04-15T12:00:00Z")),
+        // Missing fields default to the start of the period
+        ("D:20230415", Some("2023-04-15T00:00:00Z")),
+        ("D:2023041512", Some("2023-04-15T12:00:00Z")),
+        // Malformed
+        ("", None),
+        ("D:", None),
+        ("D:2023", None),
+        ("D:202304", None),
+        ("D:20231315120000Z", None),
+        ("D:20230431", None),
+        ("D:20230415250000Z", None),
+        ("April 15, 2023", None),
+        // Implausible: before the web or in the future
+        ("D:19700101000000Z", None),
+        ("D:00000101", None),
+        ("D:29991231", None),
+    ];
+    for (value, expected) in cases {
+        let date = from_pdf_info(value);
+        assert_eq!(date.map(|date| date.timestamp), expected.map(utc), "{:?}", value);
+        assert!(date.is_none_or(|date| date.source == DateSource::PdfInfo), "{:?}", value);
+    }
+}
+
+#[test]
+fn ignores_last_modified_of_dynamic_pages() {
+    let fetched_at = utc("2024-03-12T08:30:00Z");
+    let cases: &[(&str, Option<&str>)] = &[
+        ("Mon, 04 Mar 2024 10:00:00 GMT", Some("2024-03-04T10:00:00Z")),
+        // The time of the response itself
+        ("Tue, 12 Mar 2024 08:30:00 GMT", None),
+        ("Tue, 12 Mar 2024 08:29:30 GMT", None),
+        ("Thu, 01 Jan 1970 00:00:00 GMT", None),
+        ("2024-03-04", None),
+        ("", None),
+    ];
+    for (value, expected) in cases {
+        let date = from_last_modified(value, fetched_at);
+        assert_eq!(date.map(|date| date.timestamp), expected.map(utc), "{:?}", value);
+    }
+    let future = (Utc::now() + Duration::days(3)).to_rfc2822();
+    assert!(from_last_modified(&future, fetched_at).is_none());
+}
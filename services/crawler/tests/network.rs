@@ -0,0 +1,91 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+use crawler::error::CrawlerError;
+use crawler::fetcher;
+use crawler::network::{self, NetworkPolicy};
+use url::Url;
+
+#[test]
+fn classifies_addresses() {
+    let cases: &[(&str, bool)] = &[
+        ("93.184.216.34", true),
+        ("2606:4700::6810:85e5", true),
+        ("127.0.0.1", false),
+        ("10.1.2.3", false),
+        ("172.20.0.1", false),
+        ("192.168.1.1", false),
+        ("169.254.169.254", false),
+        ("100.64.0.1", false),
+        ("0.0.0.0", false),
+        ("224.0.0.251", false),
+        ("255.255.255.255", false),
+        ("::1", false),
+        ("::", false),
+        ("fe80::1", false),
+        ("fd00::1", false),
+        ("ff02::1", false),
+        // IPv4 inside IPv6
+        ("::ffff:127.0.0.1", false),
+        ("::ffff:93.184.216.34", true),
+        ("64:ff9b::a9fe:a9fe", false),
+        ("2002:c0a8:0101::1", false),
+    ];
+    for (address, public) in cases {
+        let address: IpAddr = address.parse().unwrap();
+        assert_eq!(network::is_public_address(address), *public, "{}", address);
+    }
+}
+
+#[test]
+fn checks_urls() {
+    let policy = NetworkPolicy::new(&[80, 443], &[] as &[&str]);
+    let cases: &[(&str, bool)] = &[
+        ("https://example.com/", true),
+        ("http://93.184.216.34/page", true),
+        ("https://example.com:8443/", false),
+        ("ftp://example.com/file", false),
+        ("file:///etc/passwd", false),
+        ("http://localhost/", false),
+        ("http://admin.localhost/", false),
+        ("http://127.0.0.1/", false),
+        ("http://169.254.169.254/latest/meta-data/", false),
+        ("http://[::1]/", false),
+        ("http://[::ffff:10.0.0.1]/", false),
+    ];
+    for (url, allowed) in cases {
+        let result = policy.check_url(&Url::parse(url).unwrap());
+        assert_eq!(result.is_ok(), *allowed, "{}: {:?}", url, result);
+        if !allowed {
+            assert!(matches!(result, Err(CrawlerError::Blocked(_))));
+        }
+    }
+}
+
+#[test]
+fn allowlist_overrides_checks() {
+    let policy = NetworkPolicy::new(&[80, 443], &["test.internal", "*.staging.internal", "10.20.0.0/16", "127.0.0.1"]);
+    let cases: &[(&str, bool)] = &[
+        ("http://test.internal:8080/", true),
+        ("http://app.staging.internal/", true),
+        ("http://staging.internal:8080/", false),
+        ("http://10.20.3.4:3000/", true),
+        ("http://10.21.0.1/", false),
+        ("http://127.0.0.1:8000/", true),
+        // Schemes stay restricted
+        ("ftp://test.internal/", false),
+    ];
+    for (url, allowed) in cases {
+        let result = policy.check_url(&Url::parse(url).unwrap());
+        assert_eq!(result.is_ok(), *allowed, "{}: {:?}", url, result);
+    }
+}
+
+#[tokio::test]
+async fn refuses_to_connect_to_private_addresses() {
+    let policy = Arc::new(NetworkPolicy::new(&[80, 443], &[] as &[&str]));
+    let client = fetcher::create_http_client(&policy).unwrap();
+
+    // Sent without checking the URL first, so only the resolver stands in the way
+    let error = client.get("http://localhost/").send().await.unwrap_err();
+    assert!(network::blocked_reason(&error).is_some(), "{:?}", error);
+}
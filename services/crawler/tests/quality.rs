@@ -0,0 +1,86 @@
+use crawler::config::{Config, QualityConfig};
+use crawler::parser;
+use crawler::quality::{PageSignals, QualityScore, QualityScorer, WeightedQualityScorer};
+
+mod common;
+use common::load;
+
+fn scorer() -> WeightedQualityScorer {
+    let config = QualityConfig { languages: vec!["en".to_string()], ..QualityConfig::default() };
+    WeightedQualityScorer::new(config, vec!["docs.example".to_string()])
+}
+
+fn score_fixture(scorer: &dyn QualityScorer, file: &str) -> QualityScore {
+    let html = load(file);
+    let page = parser::parse_webpage(&html, "https://site.example/page", 200).unwrap();
+    scorer.score(&PageSignals { page: &page, body_size: html.len(), is_duplicate: false })
+}
+
+fn points(score: &QualityScore, name: &str) -> f64 {
+    score.signals.iter().find(|signal| signal.name == name).map(|signal| signal.points).unwrap()
+}
+
+#[test]
+fn articles_outscore_link_lists() {
+    let scorer = scorer();
+    let article = score_fixture(&scorer, "blog_article.html");
+    let directory = score_fixture(&scorer, "link_directory.html");
+    assert!(article.total() > directory.total(), "{} vs {}", article, directory);
+    assert_eq!(points(&article, "text_ratio"), 15.0);
+    assert_eq!(points(&directory, "text_ratio"), 0.0);
+    assert_eq!(points(&article, "no_spam"), 3.0);
+}
+
+#[test]
+fn shipped_configuration_keeps_articles_and_rejects_link_lists() {
+    let config = Config::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/config.toml")).unwrap();
+    let scorer = WeightedQualityScorer::from_config(&config);
+    let threshold = config.min_quality_score as f64;
+
+    let directory = score_fixture(&scorer, "link_directory.html");
+    let max_points: f64 = directory.signals.iter().map(|signal| signal.max_points).sum();
+    assert_eq!(max_points, 100.0);
+    assert!(directory.total() < threshold, "link_directory.html scores {:.1}: {}", directory.total(), directory);
+
+    for file in ["blog_article.html", "docs_page.html", "news_article.html", "table_layout.html"] {
+        let score = score_fixture(&scorer, file);
+        assert!(score.total() >= threshold, "{} scores {:.1}: {}", file, score.total(), score);
+    }
+}
+
+#[test]
+fn penalizes_spam_duplicates_and_other_languages() {
+    let html = format!(
+        "<html lang=\"de-AT\"><head><title>Online Casino</title></head><body><article><p>{}</p></article></body></html>",
+        "Die besten Angebote im Online Casino mit Free Spins und noch mehr Text für diese Seite hier. ".repeat(20),
+    );
+    let page = parser::parse_webpage(&html, "https://spam.example/", 200).unwrap();
+    let score = scorer().score(&PageSignals { page: &page, body_size: html.len(), is_duplicate: true });
+
+    assert_eq!(points(&score, "language"), 0.0);
+    assert_eq!(points(&score, "uniqueness"), 0.0);
+    assert_eq!(points(&score, "no_spam"), 0.0);
+    // The breakdown names every signal
+    assert!(score.to_string().contains("no_spam 0.0/3"), "{}", score);
+}
+
+#[test]
+fn counts_words_of_unspaced_scripts() {
+    let text = "搜索引擎通过网络爬虫抓取网页内容并建立索引。".repeat(60);
+    let html = format!("<html lang=\"zh\"><head><title>搜索引擎</title></head><body><article><p>{}</p></article></body></html>", text);
+    let page = parser::parse_webpage(&html, "https://site.example/zh", 200).unwrap();
+    let score = scorer().score(&PageSignals { page: &page, body_size: html.len(), is_duplicate: false });
+
+    assert_eq!(points(&score, "content_length"), 25.0, "{}", score);
+}
+
+#[test]
+fn documents_carry_the_links_weight_in_their_length() {
+    let text = "A plain text report with enough words to matter. ".repeat(200);
+    let page = parser::parse_plain_text(&text, "https://docs.example/report.txt", 200).unwrap();
+    let score = scorer().score(&PageSignals { page: &page, body_size: text.len(), is_duplicate: false });
+
+    assert_eq!(points(&score, "content_length"), 35.0);
+    assert!(score.signals.iter().all(|signal| signal.name != "links"));
+    assert_eq!(points(&score, "priority_domain"), 20.0);
+}
@@ -0,0 +1,90 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use crawler::fetcher;
+use crawler::network::NetworkPolicy;
+use crawler::robots::{RobotsChecker, RobotsVerdict};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const USER_AGENT: &str = "SonomaBot";
+
+/// Serves `robots` as the answer to every request, after `delay`, and returns
+/// the server's origin and a count of the requests it received.
+async fn serve(robots: &'static str, delay: Duration) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let origin = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            counter.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request).await;
+                tokio::time::sleep(delay).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    robots.len(),
+                    robots,
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    (origin, requests)
+}
+
+fn checker() -> RobotsChecker {
+    let network = Arc::new(NetworkPolicy::new(&[80, 443], &["127.0.0.1"]));
+    let client = fetcher::create_http_client(&network).unwrap();
+    RobotsChecker::new(client, network)
+}
+
+#[tokio::test]
+async fn applies_the_robots_txt_of_each_port() {
+    let (strict, _) = serve("User-agent: *\nDisallow: /private\n", Duration::ZERO).await;
+    let (lenient, _) = serve("User-agent: *\nAllow: /\nCrawl-delay: 5\n", Duration::ZERO).await;
+    let checker = checker();
+
+    let cases: &[(&str, &str, RobotsVerdict, Option<Duration>)] = &[
+        (&strict, "/private/page", RobotsVerdict::Disallowed, None),
+        (&strict, "/public", RobotsVerdict::Allowed, None),
+        (&lenient, "/private/page", RobotsVerdict::Allowed, Some(Duration::from_secs(5))),
+    ];
+    for (origin, path, verdict, crawl_delay) in cases {
+        let url = format!("{}{}", origin, path);
+        assert_eq!(checker.check(&url, USER_AGENT).await, (*verdict, *crawl_delay), "{}", url);
+    }
+}
+
+#[tokio::test]
+async fn slow_robots_txt_holds_up_only_its_own_host() {
+    let (slow, slow_requests) = serve("User-agent: *\nAllow: /\n", Duration::from_secs(2)).await;
+    let (fast, _) = serve("User-agent: *\nAllow: /\n", Duration::ZERO).await;
+    let checker = Arc::new(checker());
+
+    let waiting: Vec<_> = (0..3)
+        .map(|i| {
+            let checker = checker.clone();
+            let url = format!("{}/page{}", slow, i);
+            tokio::spawn(async move { checker.check(&url, USER_AGENT).await })
+        })
+        .collect();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let url = format!("{}/page", fast);
+    let check = checker.check(&url, USER_AGENT);
+    let (verdict, _) = tokio::time::timeout(Duration::from_secs(1), check).await
+        .expect("check of another host waited for the slow robots.txt");
+    assert_eq!(verdict, RobotsVerdict::Allowed);
+
+    for check in waiting {
+        assert_eq!(check.await.unwrap().0, RobotsVerdict::Allowed);
+    }
+    // Concurrent checks of the slow host shared one fetch
+    assert_eq!(slow_requests.load(Ordering::SeqCst), 1);
+}
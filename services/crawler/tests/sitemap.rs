@@ -0,0 +1,69 @@
+use crawler::sitemap::{parse_sitemap, Sitemap};
+
+fn page_locs(xml: &str) -> Vec<String> {
+    match parse_sitemap(xml.as_bytes()).expect("parses") {
+        Sitemap::Urls(entries) => entries.into_iter().map(|entry| entry.loc).collect(),
+        Sitemap::Index(locs) => panic!("expected a urlset, got an index of {:?}", locs),
+    }
+}
+
+#[test]
+fn extension_locs_do_not_replace_page_locs() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+        xmlns:image="http://www.google.com/schemas/sitemap-image/1.1"
+        xmlns:video="http://www.google.com/schemas/sitemap-video/1.1">
+  <url>
+    <loc>https://example.com/gallery</loc>
+    <lastmod>2024-05-01</lastmod>
+    <image:image>
+      <image:loc>https://cdn.example/pic.jpg</image:loc>
+    </image:image>
+  </url>
+  <url>
+    <video:video>
+      <video:thumbnail_loc>https://cdn.example/thumb.jpg</video:thumbnail_loc>
+      <video:content_loc>https://cdn.example/clip.mp4</video:content_loc>
+      <video:player_loc>https://cdn.example/player</video:player_loc>
+    </video:video>
+    <loc>https://example.com/watch</loc>
+    <priority>0.8</priority>
+  </url>
+</urlset>"#;
+
+    let entries = match parse_sitemap(xml.as_bytes()).expect("parses") {
+        Sitemap::Urls(entries) => entries,
+        Sitemap::Index(_) => panic!("expected a urlset"),
+    };
+    let locs: Vec<&str> = entries.iter().map(|entry| entry.loc.as_str()).collect();
+    assert_eq!(locs, ["https://example.com/gallery", "https://example.com/watch"]);
+    assert!(entries[0].lastmod.is_some());
+    assert_eq!(entries[1].priority, Some(0.8));
+}
+
+#[test]
+fn parses_sitemaps_without_namespace() {
+    let xml = "<urlset><url><loc>https://example.com/a</loc></url><url><loc> https://example.com/b </loc></url></urlset>";
+    assert_eq!(page_locs(xml), ["https://example.com/a", "https://example.com/b"]);
+}
+
+#[test]
+fn ignores_locs_of_other_namespaces() {
+    let xml = r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" xmlns:x="urn:example">
+  <url><x:loc>https://cdn.example/other</x:loc></url>
+  <url><loc>https://example.com/page</loc><x:loc>https://cdn.example/other</x:loc></url>
+</urlset>"#;
+    assert_eq!(page_locs(xml), ["https://example.com/page"]);
+}
+
+#[test]
+fn parses_sitemap_indexes() {
+    let xml = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap><loc>https://example.com/sitemap-1.xml</loc></sitemap>
+  <sitemap><loc>https://example.com/sitemap-2.xml</loc><lastmod>2024-05-01</lastmod></sitemap>
+</sitemapindex>"#;
+    match parse_sitemap(xml.as_bytes()).expect("parses") {
+        Sitemap::Index(locs) => assert_eq!(locs, ["https://example.com/sitemap-1.xml", "https://example.com/sitemap-2.xml"]),
+        Sitemap::Urls(_) => panic!("expected an index"),
+    }
+}
@@ -0,0 +1,138 @@
+use crawler::structured_data;
+use scraper::Html;
+use serde_json::{json, Map, Value};
+
+fn extract(head: &str, body: &str) -> Map<String, Value> {
+    structured_data::extract(&Html::parse_document(&format!("<html><head>{}</head><body>{}</body></html>", head, body)))
+}
+
+struct Case {
+    name: &'static str,
+    head: &'static str,
+    body: &'static str,
+    /// Expected flat fields, as (field, value); fields not listed must be absent
+    fields: &'static [(&'static str, &'static str)],
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "opengraph and article tags",
+        head: r#"<meta property="og:site_name" content=" Example News ">
+            <meta property="article:author" content="Ana Lopez">
+            <meta property="article:published_time" content="2024-03-12T08:00:00Z">
+            <meta property="og:updated_time" content="2024-03-13">"#,
+        body: "",
+        fields: &[
+            ("site_name", "Example News"),
+            ("author", "Ana Lopez"),
+            ("published_time", "2024-03-12T08:00:00Z"),
+            ("modified_time", "2024-03-13"),
+        ],
+    },
+    Case {
+        name: "meta author wins over article:author",
+        head: r#"<meta name="author" content="Meta Author"><meta property="article:author" content="Article Author">"#,
+        body: "",
+        fields: &[("author", "Meta Author")],
+    },
+    Case {
+        name: "json-ld @graph",
+        head: r#"<script type="application/ld+json">{"@context": "https://schema.org", "@graph": [
+                {"@type": "WebSite", "name": "Graph Site"},
+                {"@type": "Article", "author": {"@type": "Person", "name": "Graph Author"},
+                 "datePublished": "2023-11-02", "dateModified": "2023-11-05"}
+            ]}</script>"#,
+        body: "",
+        fields: &[
+            ("site_name", "Graph Site"),
+            ("author", "Graph Author"),
+            ("published_time", "2023-11-02"),
+            ("modified_time", "2023-11-05"),
+        ],
+    },
+    Case {
+        name: "json-ld top-level array and publisher as site name",
+        head: r#"<script type=" Application/LD+JSON ">[
+                {"@type": "NewsArticle", "publisher": {"@type": "Organization", "name": "Daily Paper"},
+                 "author": [{"name": ""}, {"name": "Second Author"}]}
+            ]</script>"#,
+        body: "",
+        fields: &[("site_name", "Daily Paper"), ("author", "Second Author")],
+    },
+    Case {
+        name: "invalid json-ld is ignored",
+        head: r#"<script type="application/ld+json">{"author": "Broken",</script>"#,
+        body: "",
+        fields: &[],
+    },
+    Case {
+        name: "microdata",
+        head: "",
+        body: r#"<div itemscope itemtype="https://schema.org/BlogPosting">
+                <span itemprop="author" itemscope itemtype="https://schema.org/Person"><span itemprop="name">Micro Author</span></span>
+                <time itemprop="datePublished" datetime="2022-01-15">15 January</time>
+                <meta itemprop="dateModified" content="2022-01-20">
+            </div>"#,
+        fields: &[
+            ("author", "Micro Author"),
+            ("published_time", "2022-01-15"),
+            ("modified_time", "2022-01-20"),
+        ],
+    },
+];
+
+#[test]
+fn picks_facts_from_every_source() {
+    for case in CASES {
+        let data = extract(case.head, case.body);
+        for field in ["site_name", "author", "published_time", "modified_time"] {
+            let expected = case.fields.iter().find(|(name, _)| *name == field).map(|(_, value)| json!(value));
+            assert_eq!(data.get(field), expected.as_ref(), "{}: {}", case.name, field);
+        }
+    }
+}
+
+#[test]
+fn keeps_raw_sets() {
+    let data = extract(
+        r#"<meta property="og:image" content="https://example.com/a.png">
+        <meta property="og:image" content="https://example.com/b.png">
+        <meta name="twitter:card" content="summary">
+        <script type="application/ld+json">{"@graph": [{"@type": "WebSite"}, {"@type": "Article"}]}</script>"#,
+        "",
+    );
+    assert_eq!(data["opengraph"]["image"], json!(["https://example.com/a.png", "https://example.com/b.png"]));
+    assert_eq!(data["twitter"]["card"], "summary");
+    assert_eq!(data["json_ld"], json!([{"@type": "WebSite"}, {"@type": "Article"}]));
+    assert!(!data.contains_key("microdata"));
+}
+
+#[test]
+fn nested_microdata_items_keep_their_own_properties() {
+    let data = extract("", r#"<div itemscope itemtype="https://schema.org/Recipe">
+        <h1 itemprop="name">Soup</h1>
+        <div itemprop="author" itemscope itemtype="https://schema.org/Person"><span itemprop="name">Cook</span></div>
+        <a itemprop="url" href=" https://example.com/soup ">Soup</a>
+        <span itemprop="keywords">warm</span><span itemprop="keywords">easy</span>
+    </div>"#);
+    let items = data["microdata"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["type"], "https://schema.org/Recipe");
+    let properties = &items[0]["properties"];
+    assert_eq!(properties["name"], "Soup");
+    assert_eq!(properties["author"]["properties"]["name"], "Cook");
+    assert_eq!(properties["url"], "https://example.com/soup");
+    assert_eq!(properties["keywords"], json!(["warm", "easy"]));
+}
+
+#[test]
+fn survives_deeply_nested_microdata() {
+    let depth = 5_000;
+    let body = format!(
+        r#"<div itemscope>{}<span itemprop="name">Deep</span>{}</div>"#,
+        "<div><span>".repeat(depth),
+        "</span></div>".repeat(depth),
+    );
+    let data = extract("", &body);
+    assert_eq!(data["microdata"][0]["properties"]["name"], "Deep");
+}
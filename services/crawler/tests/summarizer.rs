@@ -0,0 +1,77 @@
+use crawler::summarizer::{page_summary, sentences, summarize};
+
+#[test]
+fn splits_sentences() {
+    let cases: &[(&str, &[&str])] = &[
+        ("", &[]),
+        ("No closing period", &["No closing period"]),
+        ("First sentence. Second one! A third?", &["First sentence.", "Second one!", "A third?"]),
+        // Decimals and domain names do not end a sentence
+        ("Prices rose 3.5 percent. See example.com for details.", &["Prices rose 3.5 percent.", "See example.com for details."]),
+        ("  Über den Wolken.   Grenzenlos   ", &["Über den Wolken.", "Grenzenlos"]),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(sentences(text), *expected, "{:?}", text);
+    }
+}
+
+#[test]
+fn keeps_abbreviations_and_initials_inside_sentences() {
+    let cases: &[(&str, &[&str])] = &[
+        ("Dr. Smith arrived. He sat down.", &["Dr. Smith arrived.", "He sat down."]),
+        ("See Fig. 3 for the setup. It is simple.", &["See Fig. 3 for the setup.", "It is simple."]),
+        ("The firm moved to the U.S. Navy yard. Then it closed.", &["The firm moved to the U.S. Navy yard.", "Then it closed."]),
+        ("J. R. R. Tolkien wrote it. It sold well.", &["J. R. R. Tolkien wrote it.", "It sold well."]),
+        ("Founded by Acme Inc. Today it is large.", &["Founded by Acme Inc. Today it is large."]),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(sentences(text), *expected, "{:?}", text);
+    }
+}
+
+#[test]
+fn truncates_long_descriptions_at_character_boundaries() {
+    let cases: &[(&str, usize, &str)] = &[
+        ("Über den Wolken muss die Freiheit wohl grenzenlos sein, singt er im Lied.", 20, "Über den Wolken…"),
+        // Trailing punctuation before the cut is dropped
+        ("Größe, Gewicht, Farbe und Preis werden auf dieser Seite für jedes Modell genannt.", 17, "Größe, Gewicht…"),
+        // Without whitespace the cut falls between characters
+        ("搜索引擎通过网络爬虫抓取网页内容并建立索引。搜索引擎通过网络爬虫抓取网页内容并建立索引。搜索引擎通过网络爬虫抓取网页内容并建立索引。", 10, "搜索引擎通过网络爬…"),
+        ("Emoji 🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀🦀", 9, "Emoji…"),
+    ];
+    for (description, max_chars, expected) in cases {
+        let summary = page_summary("", Some(description), *max_chars);
+        assert_eq!(summary, *expected, "{:?} at {}", description, max_chars);
+        assert!(summary.chars().count() <= *max_chars, "{:?} at {}", description, max_chars);
+    }
+}
+
+#[test]
+fn prefers_real_meta_descriptions() {
+    let content = "Crawlers fetch pages from many hosts. Indexers store the extracted text for search.";
+    let description = "A   detailed description of how the crawler and the indexer work together.";
+    assert_eq!(page_summary(content, Some(description), 200), "A detailed description of how the crawler and the indexer work together.");
+    // Placeholder descriptions lose against the content
+    assert_eq!(page_summary(content, Some("Home"), 200), content);
+    assert_eq!(page_summary(content, None, 200), content);
+}
+
+#[test]
+fn summarizes_within_the_budget_in_document_order() {
+    let content = "Search engines crawl pages. \
+        Search engines index crawled pages for fast search. \
+        The weather was nice yesterday afternoon. \
+        Ranking orders indexed pages by search relevance.";
+    for max_chars in [0, 10, 40, 60, 100, 200] {
+        let summary = summarize(content, max_chars);
+        assert!(summary.chars().count() <= max_chars, "{} at {}", summary, max_chars);
+    }
+    assert_eq!(summarize(content, 0), "");
+    // A single sentence longer than the budget is cut
+    assert!(summarize(content, 10).ends_with('…'));
+
+    let summary = summarize(content, 100);
+    let positions: Vec<usize> = sentences(&summary).iter().map(|s| content.find(s).unwrap()).collect();
+    assert!(positions.windows(2).all(|pair| pair[0] < pair[1]), "{}", summary);
+    assert!(!summary.contains("weather"), "{}", summary);
+}
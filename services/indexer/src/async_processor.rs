@@ -3,7 +3,7 @@ use tokio::sync::Semaphore;
 use sqlx::PgPool;
 use elasticsearch::Elasticsearch;
 use crate::db_indexer::{fetch_unprocessed_docs, mark_as_processed};
-use crate::elastic_search_storage::store_processed_document_in_es;
+use crate::elastic_search_storage::{delete_document_from_es, store_processed_document_in_es};
 use crate::content_processing::process_content;
 use log::{info, error, debug};
 use uuid::Uuid;
@@ -71,6 +71,20 @@ pub async fn concurrent_process_docs(
                 }
             };
 
+            // Near-duplicates are represented in the index by another page
+            if let Some(representative) = doc.duplicate_of {
+                debug!("Doc {} is a near-duplicate of {}, removing it from the index", doc_uuid, representative);
+                let removed = match delete_document_from_es(&client, doc_uuid).await {
+                    Ok(()) => mark_as_processed(&pool, doc_uuid).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = removed {
+                    error!("Error removing near-duplicate {}: {}", doc_uuid, e);
+                    metrics_for_task.increment_index_errors();
+                }
+                return;
+            }
+
             match process_content(&doc) {
                 Ok(processed_doc) => {
                     if let Err(e) = store_processed_document_in_es(&client, &processed_doc).await {
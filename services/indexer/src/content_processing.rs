@@ -4,13 +4,15 @@ use crate::document_models::{HtmlDocs, ProcessedDoc};
 use chrono::Utc;
 use crate::error::{IndexerError, IndexerResult};
 use serde_json::json;
+use std::collections::HashSet;
 
 pub fn process_content(doc: &HtmlDocs) -> IndexerResult<ProcessedDoc> {
-    // Primary content (most important for search)
+    // Primary content (most important for search). Pages crawled before the
+    // full text was kept only have their summary
     let primary_content = format!(
         "{} {}", 
         doc.title.as_deref().unwrap_or(""),
-        doc.content_summary.as_deref().unwrap_or("")
+        doc.content.as_deref().or(doc.content_summary.as_deref()).unwrap_or("")
     );
 
     // Meta content (supplementary search info)
@@ -26,8 +28,18 @@ pub fn process_content(doc: &HtmlDocs) -> IndexerResult<ProcessedDoc> {
     // Process meta content with lower weight
     let meta_words = process_text(&meta_content)?;
 
-    let primary_words_clone = primary_words.clone();
-    let mut all_words = primary_words;
+    // Keywords come from the title and meta tags only; the full text would
+    // turn every word of the page into a keyword
+    let title_words = process_text(doc.title.as_deref().unwrap_or(""))?;
+    let mut keywords: Vec<String> = Vec::new();
+    for word in title_words.into_iter().chain(meta_words.iter().cloned()) {
+        if !keywords.contains(&word) {
+            keywords.push(word);
+        }
+    }
+
+    let primary_words_clone: HashSet<&String> = primary_words.iter().collect();
+    let mut all_words = primary_words.clone();
     all_words.extend(meta_words.iter().cloned().filter(|w| !primary_words_clone.contains(w)));
 
     Ok(ProcessedDoc {
@@ -38,14 +50,25 @@ pub fn process_content(doc: &HtmlDocs) -> IndexerResult<ProcessedDoc> {
         processed_doc_metadata: Some(json!({
             "domain": doc.domain,
             "meta_description": doc.meta_description,
-            "meta_keywords": doc.meta_keywords
+            "meta_keywords": doc.meta_keywords,
+            "language": page_metadata(doc, "language"),
+            "site_name": page_metadata(doc, "site_name"),
+            "author": page_metadata(doc, "author"),
+            "published_time": page_metadata(doc, "published_time")
         })),
         processed_doc_content_summary: doc.content_summary.clone(),
-        processed_doc_keywords: Some(all_words),
-        processed_doc_page_rank: doc.page_rank
+        processed_doc_keywords: Some(keywords),
+        processed_doc_page_rank: doc.page_rank,
+        processed_doc_last_updated: doc.last_updated_timestamp
     })
 }
 
+/// A text field of the metadata the crawler extracted. Only flat fields are
+/// passed on, the raw structured data would clash with the index mapping.
+fn page_metadata<'a>(doc: &'a HtmlDocs, key: &str) -> Option<&'a str> {
+    doc.metadata.as_ref()?.get(key)?.as_str()
+}
+
 fn process_text(text: &str) -> IndexerResult<Vec<String>> {
     let en_stemmer = Stemmer::create(Algorithm::English);
     let stopwords = match Spark::stopwords(StopwordsLanguage::English) {
@@ -16,6 +16,7 @@ struct DbHtmlDoc {
     url: String,
     domain: String,
     content_summary: Option<String>,
+    content: Option<String>,
     title: Option<String>,
     meta_title: Option<String>,
     meta_description: Option<String>,
@@ -23,7 +24,9 @@ struct DbHtmlDoc {
     metadata: Option<JsonValue>,
     page_rank: Option<f64>,
     ranked: Option<bool>,
-    last_ranked_at: Option<DateTime<Utc>>
+    last_ranked_at: Option<DateTime<Utc>>,
+    last_updated_timestamp: Option<DateTime<Utc>>,
+    duplicate_of: Option<SqlxUuid>
 }
 
 const MAX_RETRIES: u32 = 3;
@@ -36,14 +39,15 @@ pub async fn fetch_unprocessed_docs(pool: &PgPool, limit: i64) -> IndexerResult<
             DbHtmlDoc,
             r#"
             SELECT 
-                id, url, domain, title, content_summary, 
-                meta_title, meta_description, meta_keywords, 
-                metadata, page_rank, 
-                COALESCE(ranked, false) as ranked,
-                last_ranked_at
-            FROM webpages
-            WHERE processed = FALSE AND ranked = TRUE
-            ORDER BY page_rank DESC
+                w.id, w.url, w.domain, w.title, w.content_summary, c.content as "content?",
+                w.meta_title, w.meta_description, w.meta_keywords, 
+                w.metadata, w.page_rank, 
+                COALESCE(w.ranked, false) as ranked,
+                w.last_ranked_at, w.last_updated_timestamp, w.duplicate_of
+            FROM webpages w
+            LEFT JOIN webpage_contents c ON c.webpage_id = w.id
+            WHERE w.processed = FALSE AND w.ranked = TRUE
+            ORDER BY w.page_rank DESC
             LIMIT $1
             "#,
             limit
@@ -60,6 +64,7 @@ pub async fn fetch_unprocessed_docs(pool: &PgPool, limit: i64) -> IndexerResult<
                         url: db_doc.url,
                         domain: db_doc.domain,
                         content_summary: db_doc.content_summary,
+                        content: db_doc.content,
                         title: db_doc.title,
                         meta_title: db_doc.meta_title,
                         meta_description: db_doc.meta_description,
@@ -68,9 +73,10 @@ pub async fn fetch_unprocessed_docs(pool: &PgPool, limit: i64) -> IndexerResult<
                         page_rank: db_doc.page_rank.unwrap_or(0.0),
                         ranked: db_doc.ranked.unwrap_or(false),
                         last_ranked_at: db_doc.last_ranked_at,
+                        duplicate_of: db_doc.duplicate_of.map(|id| Uuid::from_bytes(*id.as_bytes())),
                         content_hash: String::new(),
                         fetch_timestamp: Utc::now(),
-                        last_updated_timestamp: None,
+                        last_updated_timestamp: db_doc.last_updated_timestamp,
                         status: "pending".to_string(),
                         links: Vec::new()
                     })
@@ -87,7 +93,7 @@ pub async fn fetch_unprocessed_docs(pool: &PgPool, limit: i64) -> IndexerResult<
             ));
         }
 
-        let backoff = Duration::from_millis(2u64.pow(attempt) * 100 + rand::thread_rng().gen_range(0..100));
+        let backoff = Duration::from_millis(2u64.pow(attempt) * 100 + rand::rng().random_range(0..100));
         sleep(backoff).await;
     }
 
@@ -123,7 +129,7 @@ pub async fn mark_as_processed(pool: &PgPool, doc_id: Uuid) -> IndexerResult<()>
             ));
         }
 
-        let backoff = Duration::from_millis(2u64.pow(attempt) * 100 + rand::thread_rng().gen_range(0..100));
+        let backoff = Duration::from_millis(2u64.pow(attempt) * 100 + rand::rng().random_range(0..100));
         sleep(backoff).await;
     }
 
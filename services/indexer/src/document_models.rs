@@ -9,6 +9,8 @@ pub struct HtmlDocs {
     pub url: String,
     pub domain: String,
     pub content_summary: Option<String>,
+    /// Full extracted text of the page
+    pub content: Option<String>,
     pub title: Option<String>,
     pub meta_title: Option<String>,
     pub meta_description: Option<String>,
@@ -22,6 +24,9 @@ pub struct HtmlDocs {
     pub links: Vec<String>,
     pub ranked: bool,
     pub last_ranked_at: Option<DateTime<Utc>>,
+    /// Representative of the page's near-duplicate cluster; such pages are
+    /// kept out of the index
+    pub duplicate_of: Option<Uuid>,
 }
 
 impl HtmlDocs {
@@ -36,5 +41,7 @@ pub struct ProcessedDoc {
     pub processed_doc_metadata: Option<serde_json::Value>,
     pub processed_doc_content_summary: Option<String>,
     pub processed_doc_keywords: Option<Vec<String>>,
-    pub processed_doc_page_rank: f64
+    pub processed_doc_page_rank: f64,
+    /// When the page was last changed, as far as the crawler could tell
+    pub processed_doc_last_updated: Option<DateTime<Utc>>
 }
\ No newline at end of file
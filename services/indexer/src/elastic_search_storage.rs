@@ -1,19 +1,21 @@
 use elasticsearch::{
     Elasticsearch, 
     http::transport::Transport, 
-    indices::{IndicesCreateParts, IndicesExistsParts},
+    indices::{IndicesCreateParts, IndicesExistsParts, IndicesPutMappingParts},
     IndexParts,
+    DeleteParts,
     Error as EsError,
     cat::CatCountParts,
 };
 use crate::document_models::ProcessedDoc;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::time::Duration;
 use tokio::time::sleep;
 use crate::error::{IndexerError, IndexerResult};
 use log::{info, debug, warn, error};
 use std::env;
 use std::sync::Arc;
+use uuid::Uuid;
 
 pub async fn get_elasticsearch_client() -> IndexerResult<Elasticsearch> {
     let elasticsearch_url = env::var("ELASTICSEARCH_URL")
@@ -25,6 +27,55 @@ pub async fn get_elasticsearch_client() -> IndexerResult<Elasticsearch> {
     Ok(client)
 }
 
+/// Field mappings of the `pages` index.
+fn page_mappings() -> Value {
+    json!({
+        "properties": {
+            "webpage_id": { "type": "keyword" },
+            "domain": { "type": "keyword" },
+            "title": { 
+                "type": "text",
+                "analyzer": "standard",
+                "fields": {
+                    "keyword": {
+                        "type": "keyword",
+                        "ignore_above": 256
+                    }
+                }
+            },
+            "meta_title": { 
+                "type": "text",
+                "analyzer": "standard"
+            },
+            "meta_description": { 
+                "type": "text",
+                "analyzer": "standard"
+            },
+            "body": { 
+                "type": "text",
+                "analyzer": "standard"
+            },
+            "indexed_at": { "type": "date" },
+            "metadata": { 
+                "type": "object",
+                "properties": {
+                    "language": { "type": "keyword" },
+                    "site_name": { "type": "keyword" },
+                    "author": { "type": "keyword" },
+                    "published_time": { "type": "date", "ignore_malformed": true }
+                }
+            },
+            "content_summary": { 
+                "type": "text",
+                "index": false
+            },
+            "keywords": { "type": "keyword" },
+            "page_rank": { "type": "double" },
+            "last_updated": { "type": "date" }
+        }
+    })
+}
+
 pub async fn ensure_index_exists(client: &Elasticsearch) -> IndexerResult<()> {
     let index_name = "pages";
 
@@ -40,6 +91,7 @@ pub async fn ensure_index_exists(client: &Elasticsearch) -> IndexerResult<()> {
 
     if exists {
         info!("Index '{}' already exists", index_name);
+        update_mappings(client, index_name).await?;
     } else {
         info!("Creating index '{}'", index_name);
         // Define the index settings and mappings
@@ -55,48 +107,7 @@ pub async fn ensure_index_exists(client: &Elasticsearch) -> IndexerResult<()> {
                     }
                 }
             },
-            "mappings": {
-                "properties": {
-                    "webpage_id": { "type": "keyword" },
-                    "domain": { "type": "keyword" },
-                    "title": { 
-                        "type": "text",
-                        "analyzer": "standard",
-                        "fields": {
-                            "keyword": {
-                                "type": "keyword",
-                                "ignore_above": 256
-                            }
-                        }
-                    },
-                    "meta_title": { 
-                        "type": "text",
-                        "analyzer": "standard"
-                    },
-                    "meta_description": { 
-                        "type": "text",
-                        "analyzer": "standard"
-                    },
-                    "body": { 
-                        "type": "text",
-                        "analyzer": "standard"
-                    },
-                    "indexed_at": { "type": "date" },
-                    "metadata": { 
-                        "type": "object",
-                        "properties": {
-                            "language": { "type": "keyword" },
-                            "site_name": { "type": "keyword" }
-                        }
-                    },
-                    "content_summary": { 
-                        "type": "text",
-                        "analyzer": "standard"
-                    },
-                    "keywords": { "type": "keyword" },
-                    "page_rank": { "type": "double" }
-                }
-            }
+            "mappings": page_mappings()
         });
 
         // Create the index
@@ -117,6 +128,61 @@ pub async fn ensure_index_exists(client: &Elasticsearch) -> IndexerResult<()> {
     Ok(())
 }
 
+/// Brings the mappings of an index created by an older version up to date.
+///
+/// Fields are put one at a time, so fields added since are mapped even when
+/// another field can no longer be changed in place (an analyzed
+/// `content_summary`, say). Those are logged and keep their old mapping
+/// until the index is rebuilt, see docs/database/elasticsearch.md.
+async fn update_mappings(client: &Elasticsearch, index_name: &str) -> IndexerResult<()> {
+    let mut conflicts = 0;
+    for (field, mapping) in field_mappings(&page_mappings(), "") {
+        let response = client
+            .indices()
+            .put_mapping(IndicesPutMappingParts::Index(&[index_name]))
+            .body(mapping)
+            .send()
+            .await?;
+
+        if !response.status_code().is_success() {
+            let body = response.text().await.unwrap_or_default();
+            warn!("Mapping of '{}' in index '{}' can't be updated in place: {}", field, index_name, body);
+            conflicts += 1;
+        }
+    }
+
+    if conflicts > 0 {
+        warn!("{} fields of index '{}' keep an outdated mapping until the index is rebuilt", conflicts, index_name);
+    } else {
+        info!("Mappings of index '{}' are up to date", index_name);
+    }
+    Ok(())
+}
+
+/// Splits `{"properties": {...}}` into one single-field mapping per leaf
+/// field, named by its dotted path.
+fn field_mappings(mappings: &Value, prefix: &str) -> Vec<(String, Value)> {
+    let Some(properties) = mappings.get("properties").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+
+    let mut fields = Vec::new();
+    for (name, mapping) in properties {
+        let path = format!("{}{}", prefix, name);
+        let nested = field_mappings(mapping, &format!("{}.", path));
+        if nested.is_empty() {
+            fields.push((path, json!({ "properties": { name: mapping } })));
+        } else {
+            for (nested_path, nested_mapping) in nested {
+                let mut object = mapping.clone();
+                object["properties"] = nested_mapping["properties"].clone();
+                fields.push((nested_path, json!({ "properties": { name: object } })));
+            }
+        }
+    }
+    fields
+}
+
 const MAX_RETRIES: u32 = 3;
 
 pub async fn store_processed_document_in_es(client: &Elasticsearch, doc: &ProcessedDoc) -> IndexerResult<()> {
@@ -145,8 +211,10 @@ pub async fn store_processed_document_in_es(client: &Elasticsearch, doc: &Proces
 }
 
 async fn store_document(client: &Elasticsearch, doc: &ProcessedDoc) -> Result<(), EsError> {
+    // Keyed by webpage id, so re-crawled pages replace their previous document
+    let doc_id = doc.processed_doc_webpage_id.to_string();
     let response = client
-        .index(IndexParts::Index("pages"))
+        .index(IndexParts::IndexId("pages", &doc_id))
         .body(json!({
             "webpage_id": doc.processed_doc_webpage_id,
             "title": doc.processed_doc_title,
@@ -155,7 +223,8 @@ async fn store_document(client: &Elasticsearch, doc: &ProcessedDoc) -> Result<()
             "metadata": doc.processed_doc_metadata,
             "content_summary": doc.processed_doc_content_summary,
             "keywords": doc.processed_doc_keywords,
-            "page_rank": doc.processed_doc_page_rank
+            "page_rank": doc.processed_doc_page_rank,
+            "last_updated": doc.processed_doc_last_updated
         }))
         .send()
         .await?;
@@ -174,6 +243,26 @@ async fn store_document(client: &Elasticsearch, doc: &ProcessedDoc) -> Result<()
     Ok(())
 }
 
+/// Removes a page's document from the index. A page that was never indexed
+/// is not an error.
+pub async fn delete_document_from_es(client: &Elasticsearch, webpage_id: Uuid) -> IndexerResult<()> {
+    let doc_id = webpage_id.to_string();
+    let response = client
+        .delete(DeleteParts::IndexId("pages", &doc_id))
+        .send()
+        .await
+        .map_err(IndexerError::Elasticsearch)?;
+
+    let status = response.status_code();
+    if !status.is_success() && status.as_u16() != 404 {
+        return Err(IndexerError::Other(format!(
+            "Elasticsearch delete failed with status: {}. Document ID: {}",
+            status, webpage_id
+        )));
+    }
+    Ok(())
+}
+
 pub async fn get_elasticsearch_doc_count(client: &Arc<Elasticsearch>) -> Result<i64, IndexerError> {
     let response = client
         .cat()
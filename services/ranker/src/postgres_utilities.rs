@@ -8,13 +8,16 @@ use crate::data_models::LinkStorage;
 pub async fn fetch_links(pool: &PgPool) -> Result<Vec<LinkStorage>, Error> {
     debug!("Fetching links from database...");
     
-    // Using the existing compound index idx_links_source_target
+    // Using the existing compound index idx_links_source_target. Links to a
+    // redirecting URL count for the page it redirects to.
     let rows = sqlx::query_as::<_, LinkStorage>(
-        "SELECT source_webpage_id, target_url
-         FROM links
-         WHERE source_webpage_id IS NOT NULL 
-           AND target_url LIKE 'http%'
-         ORDER BY source_webpage_id, target_url"
+        "SELECT l.source_webpage_id, COALESCE(r.target_url, l.target_url) AS target_url
+         FROM links l
+         LEFT JOIN url_redirects r ON r.source_url = l.target_url
+         WHERE l.source_webpage_id IS NOT NULL 
+           AND l.target_url LIKE 'http%'
+           AND NOT l.rel && ARRAY['nofollow', 'ugc', 'sponsored']
+         ORDER BY l.source_webpage_id, target_url"
     )
     .fetch_all(pool)
     .await
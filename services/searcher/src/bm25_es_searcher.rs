@@ -61,14 +61,6 @@ pub async fn fetch_bm25_scores(
                                 }
                             }
                         },
-                        {
-                            "match": {
-                                "content_summary": {
-                                    "query": query,
-                                    "boost": 2.0
-                                }
-                            }
-                        },
                         {
                             "match": {
                                 "body": {
@@ -3,4 +3,15 @@ mod schema;
 
 pub use schema::Webpage;
 pub use schema::Link;
+pub use schema::FrontierEntry;
+pub use schema::FrontierBacklog;
+pub use schema::FetchLogEntry;
+pub use schema::CacheValidators;
+pub use schema::FetchHistory;
+pub use schema::SavedWebpage;
+pub use schema::RevisitEntry;
+pub use schema::NearDuplicate;
+pub use schema::JobStatus;
+pub use schema::JobCounters;
+pub use schema::CrawlJob;
 pub use postgre::PostgresStorage;
\ No newline at end of file
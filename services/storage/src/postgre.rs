@@ -1,6 +1,10 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use sqlx::{postgres::{PgPool, PgPoolOptions}};
-use crate::schema::{Webpage, Link};
+use crate::schema::{
+    Webpage, Link, FrontierEntry, FrontierBacklog, FetchLogEntry, CacheValidators, FetchHistory, SavedWebpage, RevisitEntry, NearDuplicate,
+    CrawlJob, JobCounters, JobStatus,
+};
+use std::collections::HashMap;
 use std::sync::Arc;
 use thiserror::Error;
 use uuid::Uuid;
@@ -15,6 +19,7 @@ pub enum StorageError {
     DataError(String),
 }
 
+#[derive(Clone)]
 pub struct PostgresStorage {
     pub pool: Arc<PgPool>,
 }
@@ -32,30 +37,76 @@ impl PostgresStorage {
         Ok(Self { pool: Arc::new(pool) })
     }
 
-    pub async fn save_webpage(&self, webpage: &Webpage) -> Result<(), StorageError> {
-        sqlx::query!(
+    /// Brings the schema up to date by running `init.sql`, which only creates
+    /// and adds what is missing. The compose files run it just once, when the
+    /// database volume is created, so databases of earlier versions get their
+    /// new tables, columns and indexes here. An advisory lock keeps crawlers
+    /// starting at the same time from running it concurrently.
+    pub async fn migrate(&self) -> Result<(), StorageError> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('sonoma_search_schema'))")
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::raw_sql(include_str!("scripts/init.sql"))
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    /// Inserts or refreshes a webpage together with its full extracted text
+    /// and returns its stored id, whether the content is new or changed since
+    /// the last fetch, and its fetch history.
+    ///
+    /// A re-crawl always overwrites the extracted fields with the fresh ones.
+    /// When `content_hash` differs from the stored one, `processed` is reset so
+    /// the indexer picks the page up again and the change is counted. Rank
+    /// information is kept. Page and text are written in one transaction, so
+    /// the indexer never sees a changed page with its old text.
+    pub async fn save_webpage(&self, webpage: &Webpage, content: Option<&str>) -> Result<SavedWebpage, StorageError> {
+        let mut transaction = self.pool.begin().await?;
+        let row = sqlx::query!(
             r#"
+            WITH previous AS (
+                SELECT content_hash FROM webpages WHERE url = $2
+            )
             INSERT INTO webpages (
                 id, url, domain, title, content_summary, fetch_timestamp, 
                 last_updated_timestamp, status, content_hash, metadata,
                 meta_title, meta_description, meta_keywords,
-                ranked, last_ranked_at, page_rank
+                ranked, last_ranked_at, page_rank, etag, last_modified, mime_type, simhash, fetch_count
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, 1)
             ON CONFLICT (url) DO UPDATE
             SET domain = EXCLUDED.domain,
-                title = COALESCE(webpages.title, EXCLUDED.title),
-                content_summary = COALESCE(webpages.content_summary, EXCLUDED.content_summary),
+                title = EXCLUDED.title,
+                content_summary = EXCLUDED.content_summary,
+                fetch_timestamp = EXCLUDED.fetch_timestamp,
                 last_updated_timestamp = EXCLUDED.last_updated_timestamp,
-                status = COALESCE(webpages.status, EXCLUDED.status),
-                content_hash = COALESCE(webpages.content_hash, EXCLUDED.content_hash),
-                metadata = COALESCE(webpages.metadata, EXCLUDED.metadata),
-                meta_title = COALESCE(webpages.meta_title, EXCLUDED.meta_title),
-                meta_description = COALESCE(webpages.meta_description, EXCLUDED.meta_description),
-                meta_keywords = COALESCE(webpages.meta_keywords, EXCLUDED.meta_keywords),
+                status = EXCLUDED.status,
+                content_hash = EXCLUDED.content_hash,
+                metadata = EXCLUDED.metadata,
+                meta_title = EXCLUDED.meta_title,
+                meta_description = EXCLUDED.meta_description,
+                meta_keywords = EXCLUDED.meta_keywords,
+                etag = EXCLUDED.etag,
+                last_modified = EXCLUDED.last_modified,
+                mime_type = EXCLUDED.mime_type,
+                simhash = EXCLUDED.simhash,
+                processed = CASE
+                    WHEN webpages.content_hash IS DISTINCT FROM EXCLUDED.content_hash THEN FALSE
+                    ELSE webpages.processed
+                END,
+                fetch_count = webpages.fetch_count + 1,
+                change_count = webpages.change_count + CASE
+                    WHEN webpages.content_hash IS DISTINCT FROM EXCLUDED.content_hash THEN 1
+                    ELSE 0
+                END,
                 ranked = COALESCE(webpages.ranked, EXCLUDED.ranked),
                 last_ranked_at = COALESCE(webpages.last_ranked_at, EXCLUDED.last_ranked_at),
                 page_rank = COALESCE(webpages.page_rank, EXCLUDED.page_rank)
+            RETURNING id, (SELECT content_hash FROM previous) AS previous_hash,
+                fetch_count, change_count, page_rank
             "#,
             webpage.id,
             webpage.url,
@@ -72,7 +123,426 @@ impl PostgresStorage {
             webpage.meta_keywords,
             webpage.ranked,
             webpage.last_ranked_at,
-            webpage.page_rank
+            webpage.page_rank,
+            webpage.etag,
+            webpage.last_modified,
+            webpage.mime_type,
+            webpage.simhash
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        match content {
+            Some(content) => Self::save_webpage_content(&mut transaction, row.id, content).await?,
+            None => {
+                sqlx::query!(
+                    r#"
+                    DELETE FROM webpage_contents
+                    WHERE webpage_id = $1
+                    "#,
+                    row.id
+                )
+                .execute(&mut *transaction)
+                .await?;
+            }
+        }
+        transaction.commit().await?;
+
+        let changed = row.previous_hash.is_none() || row.previous_hash != webpage.content_hash;
+        Ok(SavedWebpage {
+            id: row.id,
+            url: webpage.url.clone(),
+            content_changed: changed,
+            history: FetchHistory {
+                fetch_count: row.fetch_count,
+                change_count: row.change_count,
+                page_rank: row.page_rank.unwrap_or(0.0),
+            },
+        })
+    }
+
+    /// Moves the page stored under `old_url` to `new_url`, unless a page is
+    /// already stored there. Returns whether a page was moved.
+    pub async fn rename_webpage(&self, old_url: &str, new_url: &str) -> Result<bool, StorageError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE webpages
+            SET url = $2
+            WHERE url = $1
+              AND NOT EXISTS (SELECT 1 FROM webpages WHERE url = $2)
+            "#,
+            old_url,
+            new_url
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Refreshes the fetch timestamp of a page whose server answered
+    /// `304 Not Modified` and counts the fetch. Nothing else about the page
+    /// changes. `url` is the requested URL; the page may be stored under the
+    /// URL it redirects to or names as canonical. Returns `None` if the page
+    /// is not stored.
+    pub async fn touch_webpage(&self, url: &str) -> Result<Option<SavedWebpage>, StorageError> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE webpages
+            SET fetch_timestamp = NOW(), fetch_count = fetch_count + 1
+            WHERE url = COALESCE((SELECT target_url FROM url_redirects WHERE source_url = $1), $1)
+            RETURNING id, url, fetch_count, change_count, page_rank
+            "#,
+            url
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(row.map(|row| SavedWebpage {
+            id: row.id,
+            url: row.url,
+            content_changed: false,
+            history: FetchHistory {
+                fetch_count: row.fetch_count,
+                change_count: row.change_count,
+                page_rank: row.page_rank.unwrap_or(0.0),
+            },
+        }))
+    }
+
+    /// Sets the time at which a stored page is due to be fetched again.
+    pub async fn schedule_revisit(&self, id: Uuid, next_fetch_at: DateTime<Utc>) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            UPDATE webpages
+            SET next_fetch_at = $2
+            WHERE id = $1
+            "#,
+            id,
+            next_fetch_at
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Leases up to `limit` stored pages whose revisit is due, most overdue
+    /// first.
+    ///
+    /// Leasing pushes `next_fetch_at` forward by `lease_secs`, so a page whose
+    /// revisit is never recorded (e.g. because the crawler crashed) becomes due
+    /// again once the lease runs out. The depth is the lowest one the page has
+    /// in any frontier, if it is still in one.
+    pub async fn lease_due_revisits(&self, limit: i64, lease_secs: f64) -> Result<Vec<RevisitEntry>, StorageError> {
+        let entries = sqlx::query!(
+            r#"
+            UPDATE webpages
+            SET next_fetch_at = NOW() + make_interval(secs => $1)
+            WHERE id IN (
+                SELECT id
+                FROM webpages
+                WHERE next_fetch_at <= NOW()
+                ORDER BY next_fetch_at
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, url,
+                (SELECT MIN(depth) FROM frontier WHERE frontier.url = webpages.url) AS depth,
+                (SELECT fetch_url FROM frontier WHERE frontier.url = webpages.url AND fetch_url IS NOT NULL LIMIT 1) AS fetch_url
+            "#,
+            lease_secs,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await?
+        .into_iter()
+        .map(|row| RevisitEntry {
+            webpage_id: row.id,
+            url: row.url,
+            fetch_url: row.fetch_url,
+            depth: row.depth.unwrap_or(0),
+        })
+        .collect();
+
+        Ok(entries)
+    }
+
+    /// Makes stored pages due for a revisit right away if their content was
+    /// modified (per e.g. a sitemap `lastmod`) after they were last fetched.
+    /// Returns how many pages were brought forward.
+    pub async fn expedite_revisits(&self, urls: &[String], modified_at: &[DateTime<Utc>]) -> Result<u64, StorageError> {
+        if urls.len() != modified_at.len() {
+            return Err(StorageError::DataError("every URL needs a modification time".to_string()));
+        }
+        if urls.is_empty() {
+            return Ok(0);
+        }
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE webpages
+            SET next_fetch_at = NOW()
+            FROM UNNEST($1::text[], $2::timestamptz[]) AS m(url, modified_at)
+            WHERE webpages.url = m.url
+              AND m.modified_at > webpages.fetch_timestamp
+              AND (webpages.next_fetch_at IS NULL OR webpages.next_fetch_at > NOW())
+            "#,
+            urls,
+            modified_at
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Extends the leases of the revisits `ids` by `lease_secs` from now.
+    pub async fn renew_revisit_leases(&self, ids: &[Uuid], lease_secs: f64) -> Result<(), StorageError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE webpages
+            SET next_fetch_at = NOW() + make_interval(secs => $2)
+            WHERE id = ANY($1)
+            "#,
+            ids,
+            lease_secs
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Makes leased revisits due again immediately.
+    pub async fn release_revisit_leases(&self, ids: &[Uuid]) -> Result<(), StorageError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE webpages
+            SET next_fetch_at = NOW()
+            WHERE id = ANY($1)
+            "#,
+            ids
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Highest `page_rank` of any stored page, used to put a single page's
+    /// rank into perspective.
+    pub async fn max_page_rank(&self) -> Result<f64, StorageError> {
+        let max = sqlx::query_scalar!(
+            r#"
+            SELECT MAX(page_rank)
+            FROM webpages
+            "#
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(max.unwrap_or(0.0))
+    }
+
+    /// Finds other pages whose SimHash is within `max_distance` bits of
+    /// `simhash`, closest (then oldest) first.
+    ///
+    /// Candidates are looked up by the four 16-bit bands of the fingerprint,
+    /// which is exhaustive for distances up to 3. Larger distances only find
+    /// pages sharing a band.
+    pub async fn find_near_duplicates(&self, webpage_id: Uuid, simhash: i64, max_distance: i32, limit: i64) -> Result<Vec<NearDuplicate>, StorageError> {
+        let duplicates = sqlx::query_as!(
+            NearDuplicate,
+            r#"
+            SELECT id, url, duplicate_of, distance AS "distance!"
+            FROM (
+                SELECT id, url, duplicate_of, fetch_timestamp,
+                    bit_count((simhash # $2)::BIT(64))::INT AS distance
+                FROM webpages
+                WHERE simhash IS NOT NULL
+                  AND id <> $1
+                  AND (((simhash >> 48) & 65535) = (($2 >> 48) & 65535)
+                    OR ((simhash >> 32) & 65535) = (($2 >> 32) & 65535)
+                    OR ((simhash >> 16) & 65535) = (($2 >> 16) & 65535)
+                    OR (simhash & 65535) = ($2 & 65535))
+            ) candidates
+            WHERE distance <= $3
+            ORDER BY distance, fetch_timestamp
+            LIMIT $4
+            "#,
+            webpage_id,
+            simhash,
+            max_distance,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(duplicates)
+    }
+
+    /// Whether another page has a SimHash within `max_distance` bits of
+    /// `simhash`. The page stored under `url` and the pages in its duplicate
+    /// cluster do not count, so a page is not a duplicate of its own copies.
+    pub async fn has_near_duplicate(&self, url: &str, simhash: i64, max_distance: i32) -> Result<bool, StorageError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                FROM webpages
+                WHERE simhash IS NOT NULL
+                  AND url <> $1
+                  AND (duplicate_of IS NULL OR duplicate_of IS DISTINCT FROM (SELECT id FROM webpages WHERE url = $1))
+                  AND (((simhash >> 48) & 65535) = (($2::BIGINT >> 48) & 65535)
+                    OR ((simhash >> 32) & 65535) = (($2 >> 32) & 65535)
+                    OR ((simhash >> 16) & 65535) = (($2 >> 16) & 65535)
+                    OR (simhash & 65535) = ($2 & 65535))
+                  AND bit_count((simhash # $2)::BIT(64)) <= $3::INT
+            ) AS "exists!"
+            "#,
+            url,
+            simhash,
+            max_distance
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(row.exists)
+    }
+
+    /// Puts a page into the duplicate cluster of `representative`, or makes it
+    /// stand for itself with `None`. A page whose cluster changes is handed to
+    /// the indexer again, which only indexes representatives.
+    pub async fn set_duplicate_of(&self, webpage_id: Uuid, representative: Option<Uuid>) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            UPDATE webpages
+            SET duplicate_of = $2,
+                processed = CASE
+                    WHEN duplicate_of IS DISTINCT FROM $2 THEN FALSE
+                    ELSE processed
+                END
+            WHERE id = $1
+            "#,
+            webpage_id,
+            representative
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Takes the pages clustered under `representative` whose SimHash is now
+    /// more than `max_distance` bits away from its `simhash` out of the
+    /// cluster, so they are indexed on their own again. Returns how many left.
+    pub async fn release_diverged_duplicates(&self, representative: Uuid, simhash: i64, max_distance: i32) -> Result<u64, StorageError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE webpages
+            SET duplicate_of = NULL, processed = FALSE
+            WHERE duplicate_of = $1
+              AND (simhash IS NULL OR bit_count((simhash # $2::BIGINT)::BIT(64)) > $3::INT)
+            "#,
+            representative,
+            simhash,
+            max_distance
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Looks up the stored `ETag` / `Last-Modified` validators for `urls`,
+    /// keyed by the requested URL. A URL known to redirect to, or name as
+    /// canonical, the URL its page is stored under gets that page's
+    /// validators. Pages without any validator are left out of the result.
+    pub async fn get_cache_validators(&self, urls: &[String]) -> Result<HashMap<String, CacheValidators>, StorageError> {
+        let validators = sqlx::query!(
+            r#"
+            SELECT requested.url AS "url!", webpages.etag, webpages.last_modified
+            FROM UNNEST($1::text[]) AS requested(url)
+            LEFT JOIN url_redirects ON url_redirects.source_url = requested.url
+            JOIN webpages ON webpages.url = COALESCE(url_redirects.target_url, requested.url)
+            WHERE webpages.etag IS NOT NULL OR webpages.last_modified IS NOT NULL
+            "#,
+            urls
+        )
+        .fetch_all(&*self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.url, CacheValidators {
+            etag: row.etag,
+            last_modified: row.last_modified,
+        }))
+        .collect();
+
+        Ok(validators)
+    }
+
+    /// Appends a fetch attempt to the fetch log.
+    pub async fn log_fetch(&self, entry: &FetchLogEntry) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO fetch_log (url, final_url, redirect_chain, status, error_kind, bytes, latency_ms)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            entry.url,
+            entry.final_url,
+            &entry.redirect_chain,
+            entry.status,
+            entry.error_kind,
+            entry.bytes,
+            entry.latency_ms
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records that the page of `source_url` is stored under `target_url`,
+    /// because it redirects there (`status` is the first redirect's status
+    /// code) or names it as its canonical URL (`status` is `None`).
+    pub async fn save_redirect(&self, source_url: &str, target_url: &str, status: Option<i32>) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO url_redirects (source_url, target_url, status)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (source_url) DO UPDATE
+            SET target_url = EXCLUDED.target_url,
+                status = EXCLUDED.status,
+                updated_at = NOW()
+            "#,
+            source_url,
+            target_url,
+            status
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Forgets where the page of `source_url` is stored, once it is stored
+    /// under that URL itself again.
+    pub async fn delete_redirect(&self, source_url: &str) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM url_redirects
+            WHERE source_url = $1
+            "#,
+            source_url
         )
         .execute(&*self.pool)
         .await?;
@@ -80,6 +550,81 @@ impl PostgresStorage {
         Ok(())
     }
 
+    /// Stores the full extracted text of a page, replacing the previous one.
+    async fn save_webpage_content(
+        transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        webpage_id: Uuid,
+        content: &str,
+    ) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO webpage_contents (webpage_id, content)
+            VALUES ($1, $2)
+            ON CONFLICT (webpage_id) DO UPDATE
+            SET content = EXCLUDED.content,
+                updated_at = NOW()
+            WHERE webpage_contents.content IS DISTINCT FROM EXCLUDED.content
+            "#,
+            webpage_id,
+            content
+        )
+        .execute(&mut **transaction)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_webpage_content(&self, webpage_id: Uuid) -> Result<Option<String>, StorageError> {
+        let content = sqlx::query_scalar!(
+            r#"
+            SELECT content
+            FROM webpage_contents
+            WHERE webpage_id = $1
+            "#,
+            webpage_id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        Ok(content)
+    }
+
+    /// Removes the outgoing links of a page, before the links of a changed
+    /// version are saved.
+    pub async fn delete_links_from(&self, source_webpage_id: Uuid) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM links
+            WHERE source_webpage_id = $1
+            "#,
+            source_webpage_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Distinct targets of the stored outgoing links of the given pages.
+    pub async fn get_link_targets(&self, source_webpage_ids: &[Uuid]) -> Result<Vec<String>, StorageError> {
+        if source_webpage_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let targets = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT target_url
+            FROM links
+            WHERE source_webpage_id = ANY($1)
+            "#,
+            source_webpage_ids
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(targets)
+    }
+
     pub async fn save_link(
         &self,
         transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
@@ -87,13 +632,17 @@ impl PostgresStorage {
     ) -> Result<(), StorageError> {
         sqlx::query!(
             r#"
-            INSERT INTO links (id, source_webpage_id, target_url, anchor_text)
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO links (id, source_webpage_id, target_url, anchor_text, rel, kind, position, is_internal)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             "#,
             link.id,
             link.source_webpage_id,
             link.target_url,
             link.anchor_text,
+            &link.rel,
+            link.kind,
+            link.position,
+            link.is_internal,
         )
         .execute(&mut **transaction)
         .await?;
@@ -104,7 +653,7 @@ impl PostgresStorage {
     pub async fn get_webpage(&self, id: Uuid) -> Result<Option<Webpage>, StorageError> {
         let webpage = sqlx::query!(
             r#"
-            SELECT id, url, domain, title, content_summary, fetch_timestamp, last_updated_timestamp, status, content_hash, metadata as "metadata: Value", meta_title, meta_description, meta_keywords, ranked, last_ranked_at, page_rank
+            SELECT id, url, domain, title, content_summary, fetch_timestamp, last_updated_timestamp, status, content_hash, metadata as "metadata: Value", meta_title, meta_description, meta_keywords, ranked, last_ranked_at, page_rank, etag, last_modified, mime_type, simhash
             FROM webpages
             WHERE id = $1
             "#,
@@ -130,6 +679,10 @@ impl PostgresStorage {
             ranked: row.ranked.unwrap_or(false),
             last_ranked_at: row.last_ranked_at,
             page_rank: row.page_rank.unwrap_or(0.0),
+            etag: row.etag,
+            last_modified: row.last_modified,
+            mime_type: row.mime_type,
+            simhash: row.simhash,
         });
 
         Ok(webpage)
@@ -141,12 +694,13 @@ impl PostgresStorage {
             SELECT id, url, domain, title, content_summary, fetch_timestamp, 
                    last_updated_timestamp, status, content_hash, metadata as "metadata: Value", 
                    meta_title, meta_description, meta_keywords, 
-                   ranked, last_ranked_at, page_rank
+                   ranked, last_ranked_at, page_rank, etag, last_modified, mime_type, simhash
             FROM webpages
             WHERE to_tsvector('english', coalesce(title, '') || ' ' || 
                   coalesce(content_summary, '') || ' ' || coalesce(meta_title, '') || 
                   ' ' || coalesce(meta_description, '') || ' ' || 
                   coalesce(meta_keywords, '')) @@ plainto_tsquery('english', $1)
+              AND duplicate_of IS NULL
             ORDER BY 
                 ts_rank(to_tsvector('english', coalesce(title, '') || ' ' || 
                        coalesce(content_summary, '') || ' ' || coalesce(meta_title, '') || 
@@ -179,6 +733,10 @@ impl PostgresStorage {
             ranked: row.ranked.unwrap_or(false),
             last_ranked_at: row.last_ranked_at,
             page_rank: row.page_rank.unwrap_or(0.0),
+            etag: row.etag,
+            last_modified: row.last_modified,
+            mime_type: row.mime_type,
+            simhash: row.simhash,
         })
         .collect();
 
@@ -206,7 +764,7 @@ impl PostgresStorage {
                     last_updated_timestamp, status, content_hash, 
                     metadata as "metadata: Value", meta_title, 
                     meta_description, meta_keywords, 
-                    ranked, last_ranked_at, page_rank
+                    ranked, last_ranked_at, page_rank, etag, last_modified, mime_type, simhash
             FROM webpages
             WHERE domain = $1
             LIMIT $2
@@ -235,6 +793,10 @@ impl PostgresStorage {
             ranked: row.ranked.unwrap_or(false),
             last_ranked_at: row.last_ranked_at,
             page_rank: row.page_rank.unwrap_or(0.0),
+            etag: row.etag,
+            last_modified: row.last_modified,
+            mime_type: row.mime_type,
+            simhash: row.simhash,
         })
         .collect();
 
@@ -256,4 +818,524 @@ impl PostgresStorage {
 
         Ok(())
     }
-}
\ No newline at end of file
+    /// Adds a URL to the frontier of `job_id`. URLs that are already known to
+    /// that frontier (pending, leased or finished) are left untouched, so the
+    /// frontier doubles as the job's persistent visited set. Returns `true` if
+    /// the URL was newly inserted. `fetch_url` is the URL to request, when it
+    /// differs from the canonical `url`.
+    pub async fn enqueue_url(&self, job_id: Uuid, url: &str, fetch_url: Option<&str>, depth: i32, priority: i32) -> Result<bool, StorageError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO frontier (job_id, url, fetch_url, depth, priority)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (job_id, url) DO NOTHING
+            "#,
+            job_id,
+            url,
+            fetch_url,
+            depth,
+            priority
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Adds many URLs at the same depth to the frontier of `job_id`, each with
+    /// its own fetch URL, priority and sitemap `lastmod`. Known URLs are left untouched, as
+    /// in [`Self::enqueue_url`], apart from taking a newly given `lastmod`.
+    /// Returns how many URLs were newly inserted.
+    pub async fn enqueue_urls(
+        &self,
+        job_id: Uuid,
+        urls: &[String],
+        fetch_urls: &[Option<String>],
+        priorities: &[i32],
+        lastmods: &[Option<DateTime<Utc>>],
+        depth: i32,
+    ) -> Result<u64, StorageError> {
+        if urls.len() != fetch_urls.len() || urls.len() != priorities.len() || urls.len() != lastmods.len() {
+            return Err(StorageError::DataError("every URL needs a fetch URL, priority and lastmod".to_string()));
+        }
+        if urls.is_empty() {
+            return Ok(0);
+        }
+
+        let inserted = sqlx::query_scalar!(
+            r#"
+            WITH upserted AS (
+                INSERT INTO frontier (job_id, url, fetch_url, depth, priority, lastmod)
+                SELECT $1, url, fetch_url, $6, priority, lastmod
+                FROM UNNEST($2::text[], $3::text[], $4::int[], $5::timestamptz[]) AS t(url, fetch_url, priority, lastmod)
+                ON CONFLICT (job_id, url) DO UPDATE
+                SET lastmod = EXCLUDED.lastmod
+                WHERE EXCLUDED.lastmod IS NOT NULL
+                  AND EXCLUDED.lastmod IS DISTINCT FROM frontier.lastmod
+                RETURNING (xmax = 0) AS inserted
+            )
+            SELECT COUNT(*) FILTER (WHERE inserted) AS "count!"
+            FROM upserted
+            "#,
+            job_id,
+            urls,
+            fetch_urls as &[Option<String>],
+            priorities,
+            lastmods as &[Option<DateTime<Utc>>],
+            depth
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(inserted as u64)
+    }
+
+    /// The latest sitemap `lastmod` recorded for each of `urls`, in any job's
+    /// frontier. URLs without one are left out of the result.
+    pub async fn get_sitemap_lastmods(&self, urls: &[String]) -> Result<HashMap<String, DateTime<Utc>>, StorageError> {
+        let lastmods = sqlx::query!(
+            r#"
+            SELECT url, MAX(lastmod) AS "lastmod!"
+            FROM frontier
+            WHERE url = ANY($1) AND lastmod IS NOT NULL
+            GROUP BY url
+            "#,
+            urls
+        )
+        .fetch_all(&*self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.url, row.lastmod))
+        .collect();
+
+        Ok(lastmods)
+    }
+
+    /// Leases up to `limit` entries of the frontier of `job_id` for `lease_owner`.
+    ///
+    /// Entries are claimed with `FOR UPDATE SKIP LOCKED`, so concurrent crawlers
+    /// never receive the same URL. Leases that expired without being marked done
+    /// or failed (e.g. because the crawler crashed) are handed out again. Entries
+    /// deferred with a `not_before` time in the future are skipped.
+    pub async fn lease_frontier_batch(
+        &self,
+        job_id: Uuid,
+        lease_owner: Uuid,
+        limit: i64,
+        max_depth: i32,
+        lease_secs: f64,
+    ) -> Result<Vec<FrontierEntry>, StorageError> {
+        let entries = sqlx::query!(
+            r#"
+            UPDATE frontier
+            SET status = 'leased',
+                lease_owner = $1,
+                leased_until = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id
+                FROM frontier
+                WHERE job_id = $5
+                  AND depth <= $3
+                  AND (status = 'pending' OR (status = 'leased' AND leased_until < NOW()))
+                  AND (not_before IS NULL OR not_before <= NOW())
+                ORDER BY priority DESC, depth, discovered_at
+                LIMIT $4
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, url, fetch_url, depth, priority, discovered_at, attempts
+            "#,
+            lease_owner,
+            lease_secs,
+            max_depth,
+            limit,
+            job_id
+        )
+        .fetch_all(&*self.pool)
+        .await?
+        .into_iter()
+        .map(|row| FrontierEntry {
+            id: row.id,
+            url: row.url,
+            fetch_url: row.fetch_url,
+            depth: row.depth,
+            priority: row.priority,
+            discovered_at: row.discovered_at,
+            attempts: row.attempts,
+        })
+        .collect();
+
+        Ok(entries)
+    }
+
+    pub async fn mark_frontier_done(&self, id: Uuid) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            UPDATE frontier
+            SET status = 'done', lease_owner = NULL, leased_until = NULL, completed_at = NOW()
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn mark_frontier_failed(&self, id: Uuid, error: &str) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            UPDATE frontier
+            SET status = 'failed', lease_owner = NULL, leased_until = NULL, completed_at = NOW(),
+                attempts = attempts + 1, last_error = $2
+            WHERE id = $1
+            "#,
+            id,
+            error
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Returns a leased entry to the pending state, but not before `delay_secs`
+    /// have passed. Used for failures that are expected to go away on their own.
+    pub async fn defer_frontier_entry(&self, id: Uuid, delay_secs: f64, reason: &str) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            UPDATE frontier
+            SET status = 'pending', lease_owner = NULL, leased_until = NULL,
+                not_before = NOW() + make_interval(secs => $2),
+                attempts = attempts + 1, last_error = $3
+            WHERE id = $1
+            "#,
+            id,
+            delay_secs,
+            reason
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Extends the leases `lease_owner` holds on the frontier entries `ids` by
+    /// `lease_secs` from now. Returns how many it still held.
+    pub async fn renew_frontier_leases(&self, ids: &[Uuid], lease_owner: Uuid, lease_secs: f64) -> Result<u64, StorageError> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE frontier
+            SET leased_until = NOW() + make_interval(secs => $3)
+            WHERE id = ANY($1) AND lease_owner = $2 AND status = 'leased'
+            "#,
+            ids,
+            lease_owner,
+            lease_secs
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Returns leased entries to the pending state without counting an attempt.
+    pub async fn release_frontier_leases(&self, ids: &[Uuid]) -> Result<(), StorageError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE frontier
+            SET status = 'pending', lease_owner = NULL, leased_until = NULL
+            WHERE id = ANY($1) AND status = 'leased'
+            "#,
+            ids
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Counts the entries of the frontier of `job_id` up to `max_depth` that
+    /// are still to be crawled: those that can be leased now, and those
+    /// deferred to a later `not_before` time, with the earliest such time.
+    pub async fn count_pending_frontier(&self, job_id: Uuid, max_depth: i32) -> Result<FrontierBacklog, StorageError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                COUNT(*) FILTER (WHERE not_before IS NULL OR not_before <= NOW()) AS "ready!",
+                COUNT(*) FILTER (WHERE not_before > NOW()) AS "deferred!",
+                MIN(not_before) FILTER (WHERE not_before > NOW()) AS next_not_before
+            FROM frontier
+            WHERE job_id = $1
+              AND depth <= $2
+              AND (status = 'pending' OR (status = 'leased' AND leased_until < NOW()))
+            "#,
+            job_id,
+            max_depth
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(FrontierBacklog {
+            ready: row.ready,
+            deferred: row.deferred,
+            next_not_before: row.next_not_before,
+        })
+    }
+
+    /// Drops the frontier of a job that has finished.
+    pub async fn delete_job_frontier(&self, job_id: Uuid) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM frontier
+            WHERE job_id = $1
+            "#,
+            job_id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Drops the frontiers left behind by jobs that have finished, e.g.
+    /// because the crawler stopped while they were running.
+    pub async fn purge_finished_job_frontiers(&self) -> Result<u64, StorageError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM frontier
+            WHERE job_id IN (
+                SELECT id FROM crawl_jobs WHERE status IN ('completed', 'failed', 'cancelled')
+            )
+            "#
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+    /// Records a queued job run by the crawler process `owner`.
+    pub async fn create_crawl_job(&self, id: Uuid, owner: Uuid, params: &Value) -> Result<CrawlJob, StorageError> {
+        let row = sqlx::query_as!(
+            CrawlJobRow,
+            r#"
+            INSERT INTO crawl_jobs (id, params, owner, heartbeat_at)
+            VALUES ($1, $2, $3, NOW())
+            RETURNING id, status, params, pages_fetched, pages_stored, pages_skipped, errors, urls_deferred,
+                error, created_at, started_at, finished_at
+            "#,
+            id,
+            params,
+            owner
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        row.try_into()
+    }
+
+    pub async fn get_crawl_job(&self, id: Uuid) -> Result<Option<CrawlJob>, StorageError> {
+        let row = sqlx::query_as!(
+            CrawlJobRow,
+            r#"
+            SELECT id, status, params, pages_fetched, pages_stored, pages_skipped, errors, urls_deferred,
+                error, created_at, started_at, finished_at
+            FROM crawl_jobs
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        row.map(CrawlJob::try_from).transpose()
+    }
+
+    /// Most recently created jobs first.
+    pub async fn list_crawl_jobs(&self, limit: i64) -> Result<Vec<CrawlJob>, StorageError> {
+        sqlx::query_as!(
+            CrawlJobRow,
+            r#"
+            SELECT id, status, params, pages_fetched, pages_stored, pages_skipped, errors, urls_deferred,
+                error, created_at, started_at, finished_at
+            FROM crawl_jobs
+            ORDER BY created_at DESC
+            LIMIT $1
+            "#,
+            limit
+        )
+        .fetch_all(&*self.pool)
+        .await?
+        .into_iter()
+        .map(CrawlJob::try_from)
+        .collect()
+    }
+
+    /// Moves a queued job to `running`. Returns `false` if the job is no longer
+    /// queued, e.g. because it was cancelled while waiting.
+    pub async fn start_crawl_job(&self, id: Uuid) -> Result<bool, StorageError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE crawl_jobs
+            SET status = 'running', started_at = NOW()
+            WHERE id = $1 AND status = 'queued'
+            "#,
+            id
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Moves a running job to a final state. Jobs that were cancelled while
+    /// running keep their `cancelled` state.
+    pub async fn finish_crawl_job(&self, id: Uuid, status: JobStatus, error: Option<&str>) -> Result<(), StorageError> {
+        if !status.is_finished() {
+            return Err(StorageError::DataError(format!("{} is not a final job state", status.as_str())));
+        }
+
+        sqlx::query!(
+            r#"
+            UPDATE crawl_jobs
+            SET status = $2, error = $3, finished_at = NOW()
+            WHERE id = $1 AND status = 'running'
+            "#,
+            id,
+            status.as_str(),
+            error
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Cancels a queued or running job and returns it. Returns `None` if the
+    /// job does not exist or has already finished.
+    pub async fn cancel_crawl_job(&self, id: Uuid) -> Result<Option<CrawlJob>, StorageError> {
+        let row = sqlx::query_as!(
+            CrawlJobRow,
+            r#"
+            UPDATE crawl_jobs
+            SET status = 'cancelled', finished_at = NOW()
+            WHERE id = $1 AND status IN ('queued', 'running')
+            RETURNING id, status, params, pages_fetched, pages_stored, pages_skipped, errors, urls_deferred,
+                error, created_at, started_at, finished_at
+            "#,
+            id
+        )
+        .fetch_optional(&*self.pool)
+        .await?;
+
+        row.map(CrawlJob::try_from).transpose()
+    }
+
+    pub async fn update_crawl_job_counters(&self, id: Uuid, counters: &JobCounters) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            UPDATE crawl_jobs
+            SET pages_fetched = $2, pages_stored = $3, pages_skipped = $4, errors = $5, urls_deferred = $6
+            WHERE id = $1
+            "#,
+            id,
+            counters.pages_fetched,
+            counters.pages_stored,
+            counters.pages_skipped,
+            counters.errors,
+            counters.urls_deferred
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records that the crawler process `owner` is still working on its
+    /// queued and running jobs.
+    pub async fn heartbeat_crawl_jobs(&self, owner: Uuid) -> Result<(), StorageError> {
+        sqlx::query!(
+            r#"
+            UPDATE crawl_jobs
+            SET heartbeat_at = NOW()
+            WHERE owner = $1 AND status IN ('queued', 'running')
+            "#,
+            owner
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Fails every queued or running job whose crawler process has not
+    /// reported in for `stale_secs`: it stopped, and nobody else can resume
+    /// the job. Jobs of processes still running are left alone. Returns how
+    /// many were failed.
+    pub async fn fail_interrupted_crawl_jobs(&self, stale_secs: f64) -> Result<u64, StorageError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE crawl_jobs
+            SET status = 'failed', error = 'interrupted by crawler restart', finished_at = NOW()
+            WHERE status IN ('queued', 'running')
+              AND (heartbeat_at IS NULL OR heartbeat_at < NOW() - make_interval(secs => $1))
+            "#,
+            stale_secs
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Row shape of `crawl_jobs`, converted into a [`CrawlJob`] once the status
+/// has been validated.
+struct CrawlJobRow {
+    id: Uuid,
+    status: String,
+    params: Value,
+    pages_fetched: i32,
+    pages_stored: i32,
+    pages_skipped: i32,
+    errors: i32,
+    urls_deferred: i32,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<CrawlJobRow> for CrawlJob {
+    type Error = StorageError;
+
+    fn try_from(row: CrawlJobRow) -> Result<Self, Self::Error> {
+        let status = JobStatus::parse(&row.status)
+            .ok_or_else(|| StorageError::DataError(format!("unknown job status '{}'", row.status)))?;
+
+        Ok(CrawlJob {
+            id: row.id,
+            status,
+            params: row.params,
+            counters: JobCounters {
+                pages_fetched: row.pages_fetched,
+                pages_stored: row.pages_stored,
+                pages_skipped: row.pages_skipped,
+                errors: row.errors,
+                urls_deferred: row.urls_deferred,
+            },
+            error: row.error,
+            created_at: row.created_at,
+            started_at: row.started_at,
+            finished_at: row.finished_at,
+        })
+    }
+}
@@ -23,6 +23,15 @@ pub struct Webpage {
     pub ranked: bool,
     pub last_ranked_at: Option<DateTime<Utc>>,
     pub page_rank: f64,
+    /// `ETag` response header from the last full fetch
+    pub etag: Option<String>,
+    /// `Last-Modified` response header from the last full fetch, kept verbatim
+    /// so it can be echoed back in `If-Modified-Since`
+    pub last_modified: Option<String>,
+    /// MIME type the page was served (or sniffed) as
+    pub mime_type: Option<String>,
+    /// 64-bit SimHash of the extracted text, for near-duplicate detection
+    pub simhash: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +40,13 @@ pub struct Link {
     pub source_webpage_id: Uuid,
     pub target_url: String,
     pub anchor_text: Option<String>,
+    /// rel keywords of the link (nofollow, ugc, sponsored, next, prev)
+    pub rel: Vec<String>,
+    /// Element the link came from: a, area, iframe or link
+    pub kind: String,
+    /// Part of the page the link sits in: body, nav or footer
+    pub position: String,
+    pub is_internal: bool,
 }
 
 impl Webpage {
@@ -54,6 +70,178 @@ impl Webpage {
             ranked: false,
             last_ranked_at: None,
             page_rank: 0.0,
+            etag: None,
+            last_modified: None,
+            mime_type: None,
+            simhash: None,
         })
     }
 }
+
+/// A URL waiting in (or leased from) the persistent crawl frontier.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrontierEntry {
+    pub id: Uuid,
+    pub url: String,
+    /// The URL to request, when it differs from `url`
+    pub fetch_url: Option<String>,
+    pub depth: i32,
+    pub priority: i32,
+    pub discovered_at: DateTime<Utc>,
+    pub attempts: i32,
+}
+
+/// Entries of a job's frontier that are still to be crawled.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrontierBacklog {
+    /// Entries that can be leased right away
+    pub ready: i64,
+    /// Entries waiting for a retry until their `not_before` time
+    pub deferred: i64,
+    /// When the first deferred entry can be leased
+    pub next_not_before: Option<DateTime<Utc>>,
+}
+
+impl FrontierBacklog {
+    pub fn total(&self) -> i64 {
+        self.ready + self.deferred
+    }
+}
+
+/// One fetch attempt, as recorded in the fetch log.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FetchLogEntry {
+    /// The requested URL
+    pub url: String,
+    /// The URL the response came from, after redirects
+    pub final_url: Option<String>,
+    /// Every URL from `url` to `final_url`; empty when not redirected
+    pub redirect_chain: Vec<String>,
+    pub status: Option<i32>,
+    /// Why the fetch failed, `None` on success
+    pub error_kind: Option<String>,
+    pub bytes: Option<i64>,
+    pub latency_ms: Option<i32>,
+}
+
+/// HTTP validators of a stored page, sent back on re-crawl to allow a 304.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Fetch history of a stored page, used to plan when it is revisited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchHistory {
+    /// Number of successful fetches, including `304 Not Modified` answers
+    pub fetch_count: i32,
+    /// Number of fetches whose `content_hash` differed from the previous one
+    pub change_count: i32,
+    pub page_rank: f64,
+}
+
+/// Result of [`PostgresStorage::save_webpage`](crate::PostgresStorage::save_webpage).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedWebpage {
+    pub id: Uuid,
+    /// URL the page is stored under
+    pub url: String,
+    /// Whether the page is new or its content changed since the last fetch
+    pub content_changed: bool,
+    pub history: FetchHistory,
+}
+
+/// A known page whose revisit is due, leased for re-fetching.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisitEntry {
+    pub webpage_id: Uuid,
+    pub url: String,
+    /// The URL the page was found as, when it differs from `url`
+    pub fetch_url: Option<String>,
+    /// Depth at which the page was originally discovered
+    pub depth: i32,
+}
+
+/// A stored page whose SimHash is close to another page's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NearDuplicate {
+    pub id: Uuid,
+    pub url: String,
+    /// Representative of the page's duplicate cluster, `None` if the page
+    /// represents its cluster itself
+    pub duplicate_of: Option<Uuid>,
+    /// Hamming distance between the two SimHashes
+    pub distance: i32,
+}
+
+/// Lifecycle of a crawl job.
+///
+/// Jobs start out `Queued`, become `Running` once a crawler picks them up and
+/// end as `Completed`, `Failed` or `Cancelled`. Queued and running jobs can be
+/// cancelled; finished jobs never change state again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "completed" => Some(JobStatus::Completed),
+            "failed" => Some(JobStatus::Failed),
+            "cancelled" => Some(JobStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// Progress counters of a crawl job.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobCounters {
+    /// Pages fetched successfully, including `304 Not Modified` answers
+    pub pages_fetched: i32,
+    /// Pages saved to the webpages table
+    pub pages_stored: i32,
+    /// URLs left out on purpose, e.g. disallowed by robots.txt or low quality
+    pub pages_skipped: i32,
+    /// Fetch and processing errors
+    pub errors: i32,
+    /// URLs of the job's frontier waiting for a retry
+    pub urls_deferred: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawlJob {
+    pub id: Uuid,
+    pub status: JobStatus,
+    /// Parameters the job was submitted with
+    pub params: Value,
+    pub counters: JobCounters,
+    /// Why the job failed, if it did
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
@@ -19,9 +19,29 @@ CREATE TABLE IF NOT EXISTS webpages (
     processed BOOLEAN DEFAULT FALSE,
     ranked BOOLEAN DEFAULT FALSE,
     last_ranked_at TIMESTAMPTZ,
-    page_rank DOUBLE PRECISION DEFAULT 0.0
+    page_rank DOUBLE PRECISION DEFAULT 0.0,
+    etag TEXT,
+    last_modified TEXT,
+    mime_type TEXT,
+    simhash BIGINT,
+    duplicate_of UUID REFERENCES webpages(id) ON DELETE SET NULL,
+    fetch_count INTEGER NOT NULL DEFAULT 0,
+    change_count INTEGER NOT NULL DEFAULT 0,
+    next_fetch_at TIMESTAMPTZ
 );
 
+-- Columns added since the table was first created. CREATE TABLE IF NOT EXISTS
+-- leaves an existing table alone, so they are added here as well.
+ALTER TABLE webpages
+    ADD COLUMN IF NOT EXISTS etag TEXT,
+    ADD COLUMN IF NOT EXISTS last_modified TEXT,
+    ADD COLUMN IF NOT EXISTS fetch_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS change_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS next_fetch_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS simhash BIGINT,
+    ADD COLUMN IF NOT EXISTS duplicate_of UUID REFERENCES webpages(id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS mime_type TEXT;
+
 -- Add index for ranked status (moved up, before other indices)
 CREATE INDEX IF NOT EXISTS idx_webpages_ranked ON webpages(ranked) WHERE ranked = FALSE;
 
@@ -41,16 +61,128 @@ CREATE INDEX IF NOT EXISTS idx_webpages_domain ON webpages(domain);
 CREATE INDEX IF NOT EXISTS idx_webpages_processed ON webpages(processed) WHERE processed = FALSE;
 CREATE INDEX IF NOT EXISTS idx_webpages_metadata ON webpages USING gin(metadata);
 CREATE INDEX IF NOT EXISTS idx_webpages_page_rank ON webpages(page_rank);
+CREATE INDEX IF NOT EXISTS idx_webpages_next_fetch ON webpages(next_fetch_at) WHERE next_fetch_at IS NOT NULL;
+
+-- One index per 16-bit band of the SimHash. Two fingerprints within a Hamming
+-- distance of 3 always share at least one band exactly.
+CREATE INDEX IF NOT EXISTS idx_webpages_simhash_band0 ON webpages(((simhash >> 48) & 65535)) WHERE simhash IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_webpages_simhash_band1 ON webpages(((simhash >> 32) & 65535)) WHERE simhash IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_webpages_simhash_band2 ON webpages(((simhash >> 16) & 65535)) WHERE simhash IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_webpages_simhash_band3 ON webpages((simhash & 65535)) WHERE simhash IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_webpages_duplicate_of ON webpages(duplicate_of) WHERE duplicate_of IS NOT NULL;
+
+-- Full extracted text of each page, kept apart from webpages so that listing
+-- and ranking queries do not drag it along. lz4 compresses it in TOAST, and the
+-- low tuple target makes every text longer than a few sentences compressed.
+CREATE TABLE IF NOT EXISTS webpage_contents (
+    webpage_id UUID PRIMARY KEY REFERENCES webpages(id) ON DELETE CASCADE,
+    content TEXT COMPRESSION lz4 NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+) WITH (toast_tuple_target = 128);
 
 -- Links table
 CREATE TABLE IF NOT EXISTS links (
     id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
     source_webpage_id UUID REFERENCES webpages(id) ON DELETE CASCADE,
     target_url TEXT NOT NULL,
-    anchor_text TEXT
+    anchor_text TEXT,
+    -- rel keywords of the link: nofollow, ugc, sponsored, next, prev
+    rel TEXT[] NOT NULL DEFAULT '{}',
+    -- Element the link came from: a, area, iframe or link
+    kind TEXT NOT NULL DEFAULT 'a',
+    -- Part of the page the link sits in: body, nav or footer
+    position TEXT NOT NULL DEFAULT 'body',
+    is_internal BOOLEAN NOT NULL DEFAULT FALSE
 );
 
+ALTER TABLE links
+    ADD COLUMN IF NOT EXISTS rel TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN IF NOT EXISTS kind TEXT NOT NULL DEFAULT 'a',
+    ADD COLUMN IF NOT EXISTS position TEXT NOT NULL DEFAULT 'body',
+    ADD COLUMN IF NOT EXISTS is_internal BOOLEAN NOT NULL DEFAULT FALSE;
+
 CREATE INDEX IF NOT EXISTS idx_links_source_webpage_id ON links(source_webpage_id);
 CREATE INDEX IF NOT EXISTS idx_links_target_url ON links(target_url);
 CREATE INDEX IF NOT EXISTS idx_links_source_target ON links (source_webpage_id, target_url) 
-WHERE source_webpage_id IS NOT NULL AND target_url LIKE 'http%';
\ No newline at end of file
+WHERE source_webpage_id IS NOT NULL AND target_url LIKE 'http%';
+
+-- Crawl frontier (persistent URL queue shared across crawler restarts).
+-- Each crawl job has its own frontier; job_id is the nil UUID for the
+-- continuous seed crawl, which outlives individual jobs.
+CREATE TABLE IF NOT EXISTS frontier (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    job_id UUID NOT NULL DEFAULT uuid_nil(),
+    url TEXT NOT NULL,
+    -- The URL as found, when it differs from its canonical form in url
+    fetch_url TEXT,
+    depth INTEGER NOT NULL DEFAULT 0,
+    priority INTEGER NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'pending',
+    discovered_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    lease_owner UUID,
+    leased_until TIMESTAMPTZ,
+    not_before TIMESTAMPTZ,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    completed_at TIMESTAMPTZ,
+    -- <lastmod> of the URL in its site's sitemap
+    lastmod TIMESTAMPTZ,
+    UNIQUE (job_id, url)
+);
+
+CREATE INDEX IF NOT EXISTS idx_frontier_ready ON frontier(job_id, priority DESC, depth, discovered_at)
+WHERE status IN ('pending', 'leased');
+CREATE INDEX IF NOT EXISTS idx_frontier_url ON frontier(url);
+
+-- Crawl jobs (submitted through the crawler API, plus the initial seed crawl)
+CREATE TABLE IF NOT EXISTS crawl_jobs (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    status TEXT NOT NULL DEFAULT 'queued',
+    params JSONB NOT NULL,
+    pages_fetched INTEGER NOT NULL DEFAULT 0,
+    pages_stored INTEGER NOT NULL DEFAULT 0,
+    pages_skipped INTEGER NOT NULL DEFAULT 0,
+    errors INTEGER NOT NULL DEFAULT 0,
+    urls_deferred INTEGER NOT NULL DEFAULT 0,
+    error TEXT,
+    -- The crawler process running the job, and when it last reported in
+    owner UUID,
+    heartbeat_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_crawl_jobs_created_at ON crawl_jobs(created_at DESC);
+
+-- One row per fetch attempt, successful or not, for auditing dead links,
+-- redirects and slow hosts. redirect_chain lists every URL from the requested
+-- one to final_url when the request was redirected.
+CREATE TABLE IF NOT EXISTS fetch_log (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    url TEXT NOT NULL,
+    final_url TEXT,
+    redirect_chain TEXT[] NOT NULL DEFAULT '{}',
+    status INTEGER,
+    -- Why the fetch failed or was not made (timeout, http_status, robots_disallowed,
+    -- redirect_not_followed, ...), NULL on success
+    error_kind TEXT,
+    bytes BIGINT,
+    latency_ms INTEGER,
+    fetched_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_fetch_log_url ON fetch_log(url, fetched_at DESC);
+CREATE INDEX IF NOT EXISTS idx_fetch_log_errors ON fetch_log(fetched_at DESC) WHERE error_kind IS NOT NULL;
+
+-- URLs that redirect or name a canonical URL, mapped to the URL their page
+-- is stored under
+CREATE TABLE IF NOT EXISTS url_redirects (
+    source_url TEXT PRIMARY KEY,
+    target_url TEXT NOT NULL,
+    -- Status of the first redirect, NULL for a canonical URL
+    status INTEGER,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_url_redirects_target ON url_redirects(target_url);